@@ -0,0 +1,76 @@
+// Average frame time of the headless wave simulation as the cell count grows.
+// Run with `cargo bench --bench wave_frame_time`
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use antidotes::{
+    core::simulation::{simulate_wave, SimulationConfig, SIMULATION_FRAME_TIME},
+    npc::{
+        badcell::BadCellArchetype,
+        cell::{CellAttack, CellAttribute, CellBundle},
+        experience::CellExperience,
+        goodcell::{CellStance, GoodCellClass},
+        statuseffect::StatusEffects,
+    },
+    plugins::wave::{WaveDefinition, WaveTable},
+};
+use bevy::prelude::*;
+
+// (good cells, bad cells)
+const ARMY_SIZES: [(i32, i32); 5] = [(10, 100), (50, 500), (100, 1000), (250, 2500), (500, 5000)];
+const SIMULATED_TIME: Duration = Duration::from_secs(2);
+
+fn main() {
+    println!("{:>10} {:>10} {:>14}", "good", "bad", "ms / frame");
+
+    for (good_cell_count, bad_cell_count) in ARMY_SIZES {
+        let cell_army = (0..good_cell_count)
+            .map(|id| {
+                (
+                    id,
+                    CellBundle {
+                        // zero means a random spawn point
+                        cell_trans: Vec3::ZERO,
+                        cell_attribute: CellAttribute {
+                            // tough enough to survive the whole run, so the cell count stays put
+                            health: 1_000_000.0,
+                            immune: 1_000_000.0,
+                            infection: 0.0,
+                            cure: 0.0,
+                            infection_proof: false,
+                            cell_attack: CellAttack::new(0.5, 1.0),
+                        },
+                        stance: CellStance::HoldPosition,
+                        experience: CellExperience::default(),
+                        class: GoodCellClass::Naive,
+                        status_effects: StatusEffects::default(),
+                    },
+                )
+            })
+            .collect::<BTreeMap<_, _>>();
+        let wave_table = WaveTable {
+            waves: vec![WaveDefinition {
+                enemy_count: bad_cell_count,
+                ..WaveDefinition::from_formula(0, vec![(BadCellArchetype::Basic, 1.0)])
+            }],
+        };
+
+        let start = Instant::now();
+        let report = simulate_wave(SimulationConfig {
+            cell_army,
+            wave_table,
+            max_duration: SIMULATED_TIME,
+            ..default()
+        });
+        let frames = report.elapsed.as_secs_f64() / SIMULATION_FRAME_TIME.as_secs_f64();
+
+        println!(
+            "{:>10} {:>10} {:>14.3}",
+            good_cell_count,
+            bad_cell_count,
+            start.elapsed().as_secs_f64() * 1000.0 / frames
+        );
+    }
+}
@@ -1,8 +1,14 @@
 use bevy::prelude::*;
 
 pub mod states;
+pub mod gamerng;
+pub mod arena;
 pub mod maincamera;
+pub mod navigation;
 pub mod physics;
+pub mod simulation;
+pub mod spatialgrid;
+pub mod storage;
 pub mod userinterface;
 
 pub fn despawn_entities<T: Component>(to_despawn: Query<Entity, With<T>>, mut commands: Commands) {
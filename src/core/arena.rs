@@ -0,0 +1,435 @@
+// Every wave is fought in a generated arena: blood vessel corridors, scattered tissue walls
+// or a membrane with a few pores. The walls are colliders neither side can walk through,
+// and the bad cells come in through the openings of the arena
+use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+use rand::{rngs::StdRng, Rng};
+
+use crate::{
+    core::gamerng::GameRng,
+    npc::{
+        badcell::BadCellArchetype,
+        cell::{Cell, Collider},
+        goodcell::GoodCell,
+    },
+    plugins::{game::OnGameScreen, playerresource::PlayerResource},
+};
+
+// walls are generated within this distance of the centre
+const ARENA_EXTENT: f32 = 340.0;
+// bad cells enter this far from the centre
+const OPENING_DISTANCE: f32 = 320.0;
+// spread of the spawn points around an opening
+const OPENING_JITTER: f32 = 20.0;
+const WALL_THICKNESS: f32 = 14.0;
+// vessels meet in a square chamber of this half size
+const VESSEL_CHAMBER_HALF_SIZE: f32 = 150.0;
+const MEMBRANE_HALF_SIZE: f32 = 230.0;
+const MEMBRANE_PORE_WIDTH: f32 = 60.0;
+const TISSUE_SPAWN_POINTS: i32 = 6;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum ArenaShape {
+    // the old empty plane, bad cells come from every side
+    #[default]
+    Open,
+    // corridors along the axes that meet in a chamber in the centre
+    Vessels,
+    // loose walls scattered around the centre
+    TissueWalls,
+    // a square membrane around the centre, only crossed through its pores
+    Membrane,
+}
+
+impl ArenaShape {
+    pub fn wall_color(&self) -> Color {
+        match self {
+            ArenaShape::Open => Color::NONE,
+            ArenaShape::Vessels => Color::rgb(0.55, 0.15, 0.2),
+            ArenaShape::TissueWalls => Color::rgb(0.6, 0.45, 0.45),
+            ArenaShape::Membrane => Color::rgba(0.4, 0.6, 0.9, 0.8),
+        }
+    }
+}
+
+// An axis aligned wall
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Obstacle {
+    pub center: Vec2,
+    pub half_extents: Vec2,
+}
+
+impl Obstacle {
+    fn from_corners(min: Vec2, max: Vec2) -> Self {
+        Self {
+            center: (min + max) / 2.0,
+            half_extents: (max - min).abs() / 2.0,
+        }
+    }
+
+    // The closest position where a circle of this radius no longer overlaps the wall
+    pub fn push_out(&self, position: Vec2, radius: f32) -> Vec2 {
+        let offset = position - self.center;
+        let closest = offset.clamp(-self.half_extents, self.half_extents);
+        let outside = offset - closest;
+        let distance = outside.length();
+        if distance >= radius {
+            return position;
+        }
+        if distance > 0.0 {
+            return self.center + closest + outside / distance * radius;
+        }
+
+        // the centre is inside the wall, leave through the closest side
+        let to_side = self.half_extents - offset.abs();
+        if to_side.x < to_side.y {
+            let x = offset.x.signum() * (self.half_extents.x + radius);
+            Vec2::new(self.center.x + x, position.y)
+        } else {
+            let y = offset.y.signum() * (self.half_extents.y + radius);
+            Vec2::new(position.x, self.center.y + y)
+        }
+    }
+
+    // Whether a circle of this radius moving in a straight line from start to end
+    // touches the wall, a slab test against the wall grown by the radius
+    pub fn blocks(&self, start: Vec2, end: Vec2, radius: f32) -> bool {
+        let min = self.center - self.half_extents - radius;
+        let max = self.center + self.half_extents + radius;
+        let delta = end - start;
+        let (mut enter, mut exit) = (0.0_f32, 1.0_f32);
+        for axis in 0..2 {
+            if delta[axis].abs() <= f32::EPSILON {
+                if start[axis] < min[axis] || start[axis] > max[axis] {
+                    return false;
+                }
+                continue;
+            }
+            let to_min = (min[axis] - start[axis]) / delta[axis];
+            let to_max = (max[axis] - start[axis]) / delta[axis];
+            enter = enter.max(to_min.min(to_max));
+            exit = exit.min(to_min.max(to_max));
+            if enter > exit {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[derive(Resource, Clone, Default, Debug)]
+pub struct Arena {
+    pub shape: ArenaShape,
+    pub obstacles: Vec<Obstacle>,
+    // where the bad cells come in, empty means anywhere on the spawn ring
+    pub openings: Vec<Vec2>,
+}
+
+impl Arena {
+    pub fn generate(wave_num: i32, rng: &mut StdRng) -> Self {
+        // the first wave is always fought in the open
+        let shape = if wave_num == 0 {
+            ArenaShape::Open
+        } else {
+            match rng.gen_range(0..4) {
+                0 => ArenaShape::Open,
+                1 => ArenaShape::Vessels,
+                2 => ArenaShape::TissueWalls,
+                _ => ArenaShape::Membrane,
+            }
+        };
+
+        match shape {
+            ArenaShape::Open => Arena::default(),
+            ArenaShape::Vessels => Arena::vessels(rng),
+            ArenaShape::TissueWalls => Arena::tissue_walls(rng),
+            ArenaShape::Membrane => Arena::membrane(rng),
+        }
+    }
+
+    fn vessels(rng: &mut StdRng) -> Self {
+        let vessel_half_width = rng.gen_range(40.0..=70.0);
+        let chamber = VESSEL_CHAMBER_HALF_SIZE;
+        let mut obstacles = vec![];
+        // every quadrant is filled with an L shaped block, leaving the chamber and the vessels
+        for quadrant in [
+            Vec2::new(1.0, 1.0),
+            Vec2::new(-1.0, 1.0),
+            Vec2::new(-1.0, -1.0),
+            Vec2::new(1.0, -1.0),
+        ] {
+            obstacles.push(Obstacle::from_corners(
+                quadrant * Vec2::new(vessel_half_width, chamber),
+                quadrant * ARENA_EXTENT,
+            ));
+            obstacles.push(Obstacle::from_corners(
+                quadrant * Vec2::new(chamber, vessel_half_width),
+                quadrant * Vec2::new(ARENA_EXTENT, chamber),
+            ));
+        }
+
+        Self {
+            shape: ArenaShape::Vessels,
+            obstacles,
+            openings: vec![
+                Vec2::new(OPENING_DISTANCE, 0.0),
+                Vec2::new(0.0, OPENING_DISTANCE),
+                Vec2::new(-OPENING_DISTANCE, 0.0),
+                Vec2::new(0.0, -OPENING_DISTANCE),
+            ],
+        }
+    }
+
+    fn tissue_walls(rng: &mut StdRng) -> Self {
+        let wall_count = rng.gen_range(4..=6);
+        let obstacles: Vec<Obstacle> = (0..wall_count)
+            .map(|_| {
+                let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+                let center = Vec2::from_angle(angle) * rng.gen_range(130.0..=250.0);
+                let half_length = rng.gen_range(40.0..=80.0);
+                let half_extents = if rng.gen_bool(0.5) {
+                    Vec2::new(half_length, WALL_THICKNESS / 2.0)
+                } else {
+                    Vec2::new(WALL_THICKNESS / 2.0, half_length)
+                };
+                Obstacle {
+                    center,
+                    half_extents,
+                }
+            })
+            .collect();
+
+        // evenly spread around the ring, minus the ones a wall sits on
+        let first_angle = rng.gen_range(0.0..std::f32::consts::TAU);
+        let openings = (0..TISSUE_SPAWN_POINTS)
+            .map(|idx| {
+                let angle =
+                    first_angle + idx as f32 * std::f32::consts::TAU / TISSUE_SPAWN_POINTS as f32;
+                Vec2::from_angle(angle) * OPENING_DISTANCE
+            })
+            .filter(|opening| {
+                obstacles
+                    .iter()
+                    .all(|obstacle| obstacle.push_out(*opening, OPENING_JITTER) == *opening)
+            })
+            .collect();
+
+        Self {
+            shape: ArenaShape::TissueWalls,
+            obstacles,
+            openings,
+        }
+    }
+
+    fn membrane(rng: &mut StdRng) -> Self {
+        let mut obstacles = vec![];
+        let mut openings = vec![];
+        let half_thickness = WALL_THICKNESS / 2.0;
+        let pore_range = MEMBRANE_HALF_SIZE - MEMBRANE_PORE_WIDTH;
+        // every side is split in two by its pore, bad cells enter right outside of it
+        for normal in [Vec2::X, Vec2::Y, Vec2::NEG_X, Vec2::NEG_Y] {
+            let along = normal.perp();
+            let pore = rng.gen_range(-pore_range..=pore_range);
+            let side = normal * MEMBRANE_HALF_SIZE;
+            let pore_start = pore - MEMBRANE_PORE_WIDTH / 2.0;
+            let pore_end = pore + MEMBRANE_PORE_WIDTH / 2.0;
+            let wall_end = MEMBRANE_HALF_SIZE + half_thickness;
+            for (from, to) in [(-wall_end, pore_start), (pore_end, wall_end)] {
+                obstacles.push(Obstacle::from_corners(
+                    side + along * from - normal * half_thickness,
+                    side + along * to + normal * half_thickness,
+                ));
+            }
+            openings.push(normal * OPENING_DISTANCE + along * pore);
+        }
+
+        Self {
+            shape: ArenaShape::Membrane,
+            obstacles,
+            openings,
+        }
+    }
+
+    pub fn push_out(&self, position: Vec2, radius: f32) -> Vec2 {
+        self.obstacles.iter().fold(position, |position, obstacle| {
+            obstacle.push_out(position, radius)
+        })
+    }
+
+    pub fn is_line_clear(&self, start: Vec2, end: Vec2, radius: f32) -> bool {
+        !self
+            .obstacles
+            .iter()
+            .any(|obstacle| obstacle.blocks(start, end, radius))
+    }
+
+    // A point a bad cell comes in from, None when it may come from anywhere on the ring
+    pub fn spawn_point(&self, rng: &mut StdRng) -> Option<Vec3> {
+        if self.openings.is_empty() {
+            return None;
+        }
+        let opening = self.openings[rng.gen_range(0..self.openings.len())];
+        let jitter = Vec2::new(
+            rng.gen_range(-OPENING_JITTER..=OPENING_JITTER),
+            rng.gen_range(-OPENING_JITTER..=OPENING_JITTER),
+        );
+        Some((opening + jitter).extend(0.0))
+    }
+}
+
+// Runs before any cell is spawned, so the cells can be kept out of the walls
+pub fn generate_arena(
+    mut commands: Commands,
+    mut game_rng: ResMut<GameRng>,
+    mut arena: ResMut<Arena>,
+    player_resources: Res<PlayerResource>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    *arena = Arena::generate(player_resources.wave_num, &mut game_rng.arena);
+
+    let wall_material = materials.add(ColorMaterial::from(arena.shape.wall_color()));
+    for obstacle in arena.obstacles.iter() {
+        commands.spawn((
+            MaterialMesh2dBundle {
+                mesh: meshes
+                    .add(shape::Quad::new(obstacle.half_extents * 2.0).into())
+                    .into(),
+                material: wall_material.clone(),
+                // behind the cells
+                transform: Transform::from_translation(obstacle.center.extend(-1.0)),
+                ..default()
+            },
+            *obstacle,
+            Collider,
+            OnGameScreen,
+        ));
+    }
+}
+
+// Cells that walked into a wall are put back next to it
+#[allow(clippy::type_complexity)]
+pub fn resolve_obstacle_collisions(
+    obstacle_query: Query<&Obstacle, (With<Collider>, Without<Cell>)>,
+    mut cell_query: Query<
+        (&mut Transform, Option<&GoodCell>, Option<&BadCellArchetype>),
+        With<Cell>,
+    >,
+) {
+    if obstacle_query.is_empty() {
+        return;
+    }
+
+    for (mut cell_trans, maybe_goodcell, maybe_archetype) in cell_query.iter_mut() {
+        let radius = match (maybe_goodcell, maybe_archetype) {
+            (Some(good_cell), _) => good_cell.cell_size,
+            (None, Some(archetype)) => archetype.size(),
+            (None, None) => continue,
+        };
+        let position = cell_trans.translation.truncate();
+        let resolved = obstacle_query.iter().fold(position, |position, obstacle| {
+            obstacle.push_out(position, radius)
+        });
+        if resolved != position {
+            cell_trans.translation = resolved.extend(cell_trans.translation.z);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn wall() -> Obstacle {
+        Obstacle::from_corners(Vec2::new(-10.0, -50.0), Vec2::new(10.0, 50.0))
+    }
+
+    #[test]
+    fn the_first_wave_is_fought_in_the_open() {
+        for seed in 0..20 {
+            let arena = Arena::generate(0, &mut StdRng::seed_from_u64(seed));
+            assert_eq!(arena.shape, ArenaShape::Open);
+            assert!(arena.obstacles.is_empty());
+            assert!(arena.openings.is_empty());
+        }
+    }
+
+    #[test]
+    fn the_same_seed_generates_the_same_arena() {
+        for seed in 0..20 {
+            let first = Arena::generate(3, &mut StdRng::seed_from_u64(seed));
+            let second = Arena::generate(3, &mut StdRng::seed_from_u64(seed));
+            assert_eq!(first.shape, second.shape);
+            assert_eq!(first.openings, second.openings);
+            assert_eq!(first.obstacles.len(), second.obstacles.len());
+            for (a, b) in first.obstacles.iter().zip(second.obstacles.iter()) {
+                assert_eq!((a.center, a.half_extents), (b.center, b.half_extents));
+            }
+        }
+    }
+
+    #[test]
+    fn every_shape_is_generated_and_openings_stay_out_of_the_walls() {
+        let mut shapes = vec![];
+        for seed in 0..100 {
+            let arena = Arena::generate(1, &mut StdRng::seed_from_u64(seed));
+            for opening in arena.openings.iter() {
+                assert_eq!(arena.push_out(*opening, OPENING_JITTER), *opening);
+            }
+            if !shapes.contains(&arena.shape) {
+                shapes.push(arena.shape);
+            }
+        }
+        assert_eq!(shapes.len(), 4);
+    }
+
+    #[test]
+    fn push_out_leaves_through_the_closest_side() {
+        let wall = wall();
+        // already clear of the wall
+        assert_eq!(
+            wall.push_out(Vec2::new(30.0, 0.0), 5.0),
+            Vec2::new(30.0, 0.0)
+        );
+        // touching it from the side
+        assert_eq!(
+            wall.push_out(Vec2::new(12.0, 0.0), 5.0),
+            Vec2::new(15.0, 0.0)
+        );
+        // centre inside, the left side is closer than the top
+        assert_eq!(
+            wall.push_out(Vec2::new(-4.0, 20.0), 5.0),
+            Vec2::new(-15.0, 20.0)
+        );
+        // centre inside, the top is closer than the sides
+        assert_eq!(
+            wall.push_out(Vec2::new(2.0, 48.0), 5.0),
+            Vec2::new(2.0, 55.0)
+        );
+    }
+
+    #[test]
+    fn a_wall_blocks_lines_through_it() {
+        let wall = wall();
+        assert!(wall.blocks(Vec2::new(-40.0, 0.0), Vec2::new(40.0, 0.0), 5.0));
+        // passes over the top, but the radius still touches it
+        assert!(wall.blocks(Vec2::new(-40.0, 53.0), Vec2::new(40.0, 53.0), 5.0));
+        assert!(!wall.blocks(Vec2::new(-40.0, 60.0), Vec2::new(40.0, 60.0), 5.0));
+        // stops short of it
+        assert!(!wall.blocks(Vec2::new(-40.0, 0.0), Vec2::new(-20.0, 0.0), 5.0));
+        // parallel to it
+        assert!(!wall.blocks(Vec2::new(20.0, -80.0), Vec2::new(20.0, 80.0), 5.0));
+    }
+
+    #[test]
+    fn a_line_is_clear_when_no_wall_blocks_it() {
+        let arena = Arena {
+            shape: ArenaShape::TissueWalls,
+            obstacles: vec![wall()],
+            openings: vec![],
+        };
+        assert!(!arena.is_line_clear(Vec2::new(-40.0, 0.0), Vec2::new(40.0, 0.0), 5.0));
+        assert!(arena.is_line_clear(Vec2::new(-40.0, 80.0), Vec2::new(40.0, 80.0), 5.0));
+        assert!(Arena::default().is_line_clear(Vec2::new(-40.0, 0.0), Vec2::new(40.0, 0.0), 5.0));
+    }
+}
@@ -0,0 +1,51 @@
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::plugins::playerresource::PlayerResource;
+
+// Set this to replay a run from a bug report, e.g. ANTIDOTES_SEED=1234 cargo run
+const SEED_ENV_VAR: &str = "ANTIDOTES_SEED";
+
+// Every wave gets its own streams so a resumed run replays the same way
+const WAVE_SEED_STRIDE: u64 = 0x9E37_79B9_7F4A_7C15;
+
+// All gameplay randomness is drawn from here, split into named streams so that
+// e.g. an extra loot roll does not shift where the next wave spawns
+#[derive(Resource)]
+pub struct GameRng {
+    pub spawn: StdRng,
+    pub loot: StdRng,
+    pub combat: StdRng,
+    pub ai: StdRng,
+    pub arena: StdRng,
+}
+
+impl GameRng {
+    pub fn new(seed: u64, wave_num: i32) -> Self {
+        let wave_seed = seed.wrapping_add((wave_num as u64).wrapping_mul(WAVE_SEED_STRIDE));
+        Self {
+            spawn: StdRng::seed_from_u64(wave_seed),
+            loot: StdRng::seed_from_u64(wave_seed ^ 1),
+            combat: StdRng::seed_from_u64(wave_seed ^ 2),
+            ai: StdRng::seed_from_u64(wave_seed ^ 3),
+            arena: StdRng::seed_from_u64(wave_seed ^ 4),
+        }
+    }
+}
+
+impl Default for GameRng {
+    fn default() -> Self {
+        Self::new(new_run_seed(), 0)
+    }
+}
+
+pub fn new_run_seed() -> u64 {
+    std::env::var(SEED_ENV_VAR)
+        .ok()
+        .and_then(|seed| seed.parse().ok())
+        .unwrap_or_else(|| rand::thread_rng().gen())
+}
+
+pub fn reseed_for_wave(mut game_rng: ResMut<GameRng>, player_resources: Res<PlayerResource>) {
+    *game_rng = GameRng::new(player_resources.seed, player_resources.wave_num);
+}
@@ -0,0 +1,455 @@
+// Navigation for both sides: a flow field over the arena that leads around the walls
+// towards the other side, plus the steering that keeps a swarm from collapsing into one blob
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use bevy::prelude::*;
+
+use crate::{
+    core::{arena::Arena, spatialgrid::SpatialGrid},
+    npc::{badcell::BadCell, goodcell::GoodCell},
+};
+
+// the field covers the arena out to this distance from the centre
+const FLOW_FIELD_EXTENT: f32 = 400.0;
+pub const FLOW_FIELD_TILE_SIZE: f32 = 20.0;
+// tiles this close to a wall are treated as part of it
+const FLOW_FIELD_CLEARANCE: f32 = 8.0;
+const STRAIGHT_STEP_COST: u32 = 10;
+const DIAGONAL_STEP_COST: u32 = 14;
+
+// Steering weights, the flow towards the target counts 1
+pub const SEPARATION_RADIUS: f32 = 14.0;
+const SEPARATION_WEIGHT: f32 = 1.5;
+pub const COHESION_RADIUS: f32 = 40.0;
+const COHESION_WEIGHT: f32 = 0.2;
+// how far ahead a cell looks for walls
+const AVOIDANCE_LOOKAHEAD: f32 = 16.0;
+const AVOIDANCE_WEIGHT: f32 = 1.0;
+
+// Distance along walkable tiles from every tile to the closest cell of each side
+#[derive(Resource, Default)]
+pub struct FlowField {
+    size: i32,
+    blocked: Vec<bool>,
+    // followed by the bad cells
+    to_good_cells: Vec<u32>,
+    // followed by the good cells
+    to_bad_cells: Vec<u32>,
+}
+
+impl FlowField {
+    fn tile(&self, position: Vec2) -> Option<IVec2> {
+        let tile = ((position + Vec2::splat(FLOW_FIELD_EXTENT)) / FLOW_FIELD_TILE_SIZE)
+            .floor()
+            .as_ivec2();
+        (tile.cmpge(IVec2::ZERO).all() && tile.cmplt(IVec2::splat(self.size)).all()).then_some(tile)
+    }
+
+    fn index(&self, tile: IVec2) -> usize {
+        (tile.y * self.size + tile.x) as usize
+    }
+
+    fn tile_center(tile: IVec2) -> Vec2 {
+        (tile.as_vec2() + 0.5) * FLOW_FIELD_TILE_SIZE - Vec2::splat(FLOW_FIELD_EXTENT)
+    }
+
+    fn is_walkable(&self, tile: IVec2) -> bool {
+        tile.cmpge(IVec2::ZERO).all()
+            && tile.cmplt(IVec2::splat(self.size)).all()
+            && !self.blocked[self.index(tile)]
+    }
+
+    // The walkable neighbours of a tile with the cost of stepping there.
+    // Diagonal steps may not cut the corner of a wall
+    fn neighbours(&self, tile: IVec2) -> impl Iterator<Item = (IVec2, u32)> + '_ {
+        [
+            IVec2::X,
+            IVec2::NEG_X,
+            IVec2::Y,
+            IVec2::NEG_Y,
+            IVec2::ONE,
+            IVec2::NEG_ONE,
+            IVec2::new(1, -1),
+            IVec2::new(-1, 1),
+        ]
+        .into_iter()
+        .filter_map(move |step| {
+            let neighbour = tile + step;
+            if !self.is_walkable(neighbour) {
+                return None;
+            }
+            if step.x == 0 || step.y == 0 {
+                return Some((neighbour, STRAIGHT_STEP_COST));
+            }
+            let corners_free = self.is_walkable(tile + IVec2::new(step.x, 0))
+                && self.is_walkable(tile + IVec2::new(0, step.y));
+            corners_free.then_some((neighbour, DIAGONAL_STEP_COST))
+        })
+    }
+
+    // Marks the tiles the walls of the arena sit on, an open arena needs no field at all
+    pub fn rebuild_walls(&mut self, arena: &Arena) {
+        if arena.obstacles.is_empty() {
+            *self = FlowField::default();
+            return;
+        }
+
+        self.size = (FLOW_FIELD_EXTENT * 2.0 / FLOW_FIELD_TILE_SIZE).ceil() as i32;
+        let tile_count = (self.size * self.size) as usize;
+        self.blocked = (0..tile_count as i32)
+            .map(|idx| {
+                let center = Self::tile_center(IVec2::new(idx % self.size, idx / self.size));
+                arena.push_out(center, FLOW_FIELD_CLEARANCE) != center
+            })
+            .collect();
+        self.to_good_cells = vec![u32::MAX; tile_count];
+        self.to_bad_cells = vec![u32::MAX; tile_count];
+    }
+
+    pub fn rebuild_distances(
+        &mut self,
+        good_cells: impl Iterator<Item = Vec2>,
+        bad_cells: impl Iterator<Item = Vec2>,
+    ) {
+        if self.size == 0 {
+            return;
+        }
+
+        self.to_good_cells = self.distances_from(good_cells);
+        self.to_bad_cells = self.distances_from(bad_cells);
+    }
+
+    // Dijkstra outwards from every target at once
+    fn distances_from(&self, targets: impl Iterator<Item = Vec2>) -> Vec<u32> {
+        let mut distances = vec![u32::MAX; self.blocked.len()];
+        let mut frontier = BinaryHeap::new();
+        for target in targets {
+            let Some(tile) = self.tile(target) else {
+                continue;
+            };
+            let idx = self.index(tile);
+            if distances[idx] != 0 {
+                distances[idx] = 0;
+                frontier.push(Reverse((0, tile.x, tile.y)));
+            }
+        }
+
+        while let Some(Reverse((distance, x, y))) = frontier.pop() {
+            let tile = IVec2::new(x, y);
+            if distance > distances[self.index(tile)] {
+                continue;
+            }
+            for (neighbour, cost) in self.neighbours(tile) {
+                let neighbour_idx = self.index(neighbour);
+                if distance + cost < distances[neighbour_idx] {
+                    distances[neighbour_idx] = distance + cost;
+                    frontier.push(Reverse((distance + cost, neighbour.x, neighbour.y)));
+                }
+            }
+        }
+
+        distances
+    }
+
+    // Which way a bad cell goes from position to get closer to a good cell,
+    // None when the field does not know, e.g. in an open arena
+    pub fn towards_good_cells(&self, position: Vec2) -> Option<Vec2> {
+        self.direction(&self.to_good_cells, position)
+    }
+
+    // Same for a good cell going after the bad cells
+    pub fn towards_bad_cells(&self, position: Vec2) -> Option<Vec2> {
+        self.direction(&self.to_bad_cells, position)
+    }
+
+    // The tile centres to walk through from start to reach end around the walls, found with
+    // A* over the tiles. None when there is no way or start and end share a tile
+    pub fn find_path(&self, start: Vec2, end: Vec2) -> Option<Vec<Vec2>> {
+        let start_tile = self.tile(start)?;
+        let end_tile = self.tile(end)?;
+        if start_tile == end_tile {
+            return None;
+        }
+
+        // the cost of the shortest way with no walls in between
+        let estimate = |tile: IVec2| {
+            let offset = (end_tile - tile).abs();
+            let diagonal = offset.min_element() as u32;
+            let straight = offset.max_element() as u32 - diagonal;
+            diagonal * DIAGONAL_STEP_COST + straight * STRAIGHT_STEP_COST
+        };
+        let mut costs = vec![u32::MAX; self.blocked.len()];
+        let mut came_from: Vec<Option<IVec2>> = vec![None; self.blocked.len()];
+        let mut frontier = BinaryHeap::new();
+        costs[self.index(start_tile)] = 0;
+        frontier.push(Reverse((
+            estimate(start_tile),
+            0,
+            start_tile.x,
+            start_tile.y,
+        )));
+
+        while let Some(Reverse((_, cost, x, y))) = frontier.pop() {
+            let tile = IVec2::new(x, y);
+            if tile == end_tile {
+                break;
+            }
+            if cost > costs[self.index(tile)] {
+                continue;
+            }
+            for (neighbour, step_cost) in self.neighbours(tile) {
+                let neighbour_idx = self.index(neighbour);
+                if cost + step_cost < costs[neighbour_idx] {
+                    costs[neighbour_idx] = cost + step_cost;
+                    came_from[neighbour_idx] = Some(tile);
+                    frontier.push(Reverse((
+                        cost + step_cost + estimate(neighbour),
+                        cost + step_cost,
+                        neighbour.x,
+                        neighbour.y,
+                    )));
+                }
+            }
+        }
+
+        // walk back from the end to the first step
+        let mut path = vec![];
+        let mut step = end_tile;
+        while step != start_tile {
+            path.push(Self::tile_center(step));
+            step = came_from[self.index(step)]?;
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    fn direction(&self, distances: &[u32], position: Vec2) -> Option<Vec2> {
+        let tile = self.tile(position)?;
+        let distance = distances[self.index(tile)];
+        let (best, best_distance) = self
+            .neighbours(tile)
+            .map(|(neighbour, _)| (neighbour, distances[self.index(neighbour)]))
+            .min_by_key(|(_, neighbour_distance)| *neighbour_distance)?;
+        if best_distance == u32::MAX || best_distance >= distance {
+            return None;
+        }
+        (Self::tile_center(best) - position).try_normalize()
+    }
+}
+
+pub fn rebuild_flow_field(
+    arena: Res<Arena>,
+    mut flow_field: ResMut<FlowField>,
+    goodcell_query: Query<&Transform, With<GoodCell>>,
+    badcell_query: Query<&Transform, With<BadCell>>,
+) {
+    if arena.is_changed() {
+        flow_field.rebuild_walls(&arena);
+    }
+    flow_field.rebuild_distances(
+        goodcell_query
+            .iter()
+            .map(|good_cell_trans| good_cell_trans.translation.truncate()),
+        badcell_query
+            .iter()
+            .map(|bad_cell_trans| bad_cell_trans.translation.truncate()),
+    );
+}
+
+// Blends where a cell wants to go with keeping its distance to the cells around it,
+// staying with its group and not running into walls
+pub fn steer(
+    position: Vec3,
+    desired: Vec2,
+    neighbours: &SpatialGrid,
+    arena: &Arena,
+    radius: f32,
+) -> Vec2 {
+    let mut separation = Vec2::ZERO;
+    let mut group_center = Vec2::ZERO;
+    let mut group_size = 0;
+    for (_, neighbour_pos) in neighbours.query_radius(position, COHESION_RADIUS) {
+        let offset = (position - neighbour_pos).truncate();
+        let distance = offset.length();
+        if distance <= 0.0 {
+            continue;
+        }
+        if distance < SEPARATION_RADIUS {
+            separation += offset / distance * (1.0 - distance / SEPARATION_RADIUS);
+        }
+        group_center += neighbour_pos.truncate();
+        group_size += 1;
+    }
+    let cohesion = if group_size > 0 {
+        (group_center / group_size as f32 - position.truncate()).normalize_or_zero()
+    } else {
+        Vec2::ZERO
+    };
+
+    let ahead = position.truncate() + desired * AVOIDANCE_LOOKAHEAD;
+    let avoidance = (arena.push_out(ahead, radius) - ahead).normalize_or_zero();
+
+    (desired
+        + separation * SEPARATION_WEIGHT
+        + cohesion * COHESION_WEIGHT
+        + avoidance * AVOIDANCE_WEIGHT)
+        .normalize_or_zero()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::arena::{ArenaShape, Obstacle};
+
+    // a long wall across the middle, only passable far above or below
+    fn walled_arena() -> Arena {
+        Arena {
+            shape: ArenaShape::TissueWalls,
+            obstacles: vec![Obstacle {
+                center: Vec2::ZERO,
+                half_extents: Vec2::new(10.0, 200.0),
+            }],
+            openings: vec![],
+        }
+    }
+
+    fn flow_field(arena: &Arena) -> FlowField {
+        let mut flow_field = FlowField::default();
+        flow_field.rebuild_walls(arena);
+        flow_field
+    }
+
+    // Follows a direction tile by tile until it gives out, checking no step enters a wall
+    fn follow(arena: &Arena, start: Vec2, direction: impl Fn(Vec2) -> Option<Vec2>) -> Vec2 {
+        let mut position = start;
+        for _ in 0..200 {
+            let Some(step) = direction(position) else {
+                break;
+            };
+            position += step * FLOW_FIELD_TILE_SIZE / 2.0;
+            assert_eq!(arena.push_out(position, 0.0), position);
+        }
+        position
+    }
+
+    #[test]
+    fn an_open_arena_has_no_field() {
+        let mut flow_field = flow_field(&Arena::default());
+        flow_field.rebuild_distances([Vec2::ZERO].into_iter(), [Vec2::X * 100.0].into_iter());
+
+        assert_eq!(flow_field.towards_good_cells(Vec2::X * 100.0), None);
+        assert_eq!(flow_field.towards_bad_cells(Vec2::ZERO), None);
+        assert_eq!(flow_field.find_path(Vec2::ZERO, Vec2::X * 100.0), None);
+    }
+
+    #[test]
+    fn walls_are_marked_as_blocked() {
+        let flow_field = flow_field(&walled_arena());
+
+        assert!(!flow_field.is_walkable(flow_field.tile(Vec2::ZERO).unwrap()));
+        assert!(!flow_field.is_walkable(flow_field.tile(Vec2::new(0.0, 150.0)).unwrap()));
+        assert!(flow_field.is_walkable(flow_field.tile(Vec2::new(100.0, 0.0)).unwrap()));
+        assert!(flow_field.is_walkable(flow_field.tile(Vec2::new(0.0, 300.0)).unwrap()));
+    }
+
+    #[test]
+    fn distances_count_straight_and_diagonal_steps() {
+        let flow_field = flow_field(&walled_arena());
+        let target = Vec2::new(-200.0, 0.0);
+        let distances = flow_field.distances_from([target].into_iter());
+        let distance =
+            |offset: IVec2| distances[flow_field.index(flow_field.tile(target).unwrap() + offset)];
+
+        assert_eq!(distance(IVec2::ZERO), 0);
+        assert_eq!(distance(IVec2::new(-3, 0)), 3 * STRAIGHT_STEP_COST);
+        assert_eq!(distance(IVec2::new(0, 3)), 3 * STRAIGHT_STEP_COST);
+        assert_eq!(distance(IVec2::new(-3, -3)), 3 * DIAGONAL_STEP_COST);
+        assert_eq!(
+            distance(IVec2::new(-3, 1)),
+            DIAGONAL_STEP_COST + 2 * STRAIGHT_STEP_COST
+        );
+    }
+
+    #[test]
+    fn the_way_round_a_wall_is_longer_than_the_straight_line() {
+        let flow_field = flow_field(&walled_arena());
+        let target = Vec2::new(-50.0, 0.0);
+        let distances = flow_field.distances_from([target].into_iter());
+
+        // five tiles apart, but the wall is in between
+        let behind_wall =
+            distances[flow_field.index(flow_field.tile(Vec2::new(50.0, 0.0)).unwrap())];
+        assert!(behind_wall > 20 * STRAIGHT_STEP_COST);
+        assert!(behind_wall < u32::MAX);
+    }
+
+    #[test]
+    fn bad_cells_follow_the_field_around_the_wall() {
+        let arena = walled_arena();
+        let mut flow_field = flow_field(&arena);
+        let good_cell = Vec2::new(100.0, 0.0);
+        let bad_cell = Vec2::new(-100.0, 0.0);
+        flow_field.rebuild_distances([good_cell].into_iter(), [bad_cell].into_iter());
+
+        let first_step = flow_field.towards_good_cells(bad_cell).unwrap();
+        assert!(first_step.y.abs() > 0.5);
+        let reached = follow(&arena, bad_cell, |position| {
+            flow_field.towards_good_cells(position)
+        });
+        assert!(reached.distance(good_cell) < FLOW_FIELD_TILE_SIZE);
+
+        let reached = follow(&arena, good_cell, |position| {
+            flow_field.towards_bad_cells(position)
+        });
+        assert!(reached.distance(bad_cell) < FLOW_FIELD_TILE_SIZE);
+    }
+
+    #[test]
+    fn a_path_leads_around_the_wall() {
+        let arena = walled_arena();
+        let flow_field = flow_field(&arena);
+        let start = Vec2::new(-100.0, 20.0);
+        let end = Vec2::new(100.0, -20.0);
+
+        let path = flow_field.find_path(start, end).unwrap();
+        assert!((path[0] - start).normalize().y.abs() > 0.5);
+        assert_eq!(flow_field.tile(*path.last().unwrap()), flow_field.tile(end));
+        // every step is to a neighbouring tile and stays out of the wall
+        let mut previous = FlowField::tile_center(flow_field.tile(start).unwrap());
+        for waypoint in path {
+            assert!(waypoint.distance(previous) < FLOW_FIELD_TILE_SIZE * 1.5);
+            assert!(arena.is_line_clear(previous, waypoint, 0.0));
+            previous = waypoint;
+        }
+    }
+
+    #[test]
+    fn there_is_no_path_to_a_closed_off_place() {
+        let mut arena = walled_arena();
+        // box in the right hand side
+        arena.obstacles.extend([
+            Obstacle {
+                center: Vec2::new(200.0, 200.0),
+                half_extents: Vec2::new(210.0, 10.0),
+            },
+            Obstacle {
+                center: Vec2::new(200.0, -200.0),
+                half_extents: Vec2::new(210.0, 10.0),
+            },
+            Obstacle {
+                center: Vec2::new(400.0, 0.0),
+                half_extents: Vec2::new(10.0, 210.0),
+            },
+        ]);
+        let flow_field = flow_field(&arena);
+
+        assert_eq!(
+            flow_field.find_path(Vec2::new(-100.0, 0.0), Vec2::new(100.0, 0.0)),
+            None
+        );
+        assert_eq!(
+            flow_field.find_path(Vec2::new(-100.0, 0.0), Vec2::new(-95.0, 0.0)),
+            None
+        );
+    }
+}
@@ -0,0 +1,189 @@
+// Runs a wave without a window or renderer, so balance can be checked from tests and CI
+use std::{collections::BTreeMap, time::Duration};
+
+use bevy::{prelude::*, time::TimeUpdateStrategy};
+use serde::Serialize;
+
+use crate::{
+    core::{
+        arena::{self, Arena},
+        gamerng::GameRng,
+        navigation::FlowField,
+        spatialgrid::CellGrid,
+    },
+    npc::{
+        badcell::{self, BadCell},
+        boss::{self, Boss},
+        cell::{CellAttribute, CellBundle, CellConversionEvent},
+        experience::CellExperience,
+        goodcell::{self, CellStance, GoodCell, GoodCellClass, GoodCellId},
+        infection::InfectionModel,
+        statuseffect::StatusEffects,
+    },
+    plugins::{
+        antidote::{SubstanceResource, SubstanceTemplate},
+        combatlog::{CombatEvent, CombatLog, CombatLogPlugin, WaveEnded},
+        game,
+        playerresource::{PlayerResource, SubstanceIdGen},
+        wave::{WaveTable, WaveTableResource},
+        wavestats::RunStats,
+    },
+};
+
+pub const SIMULATION_TIMESTEP: Duration = Duration::from_micros(16_667);
+pub const SIMULATION_FRAME_TIME: Duration = Duration::from_micros(16_667);
+const SIMULATION_MAX_DURATION: Duration = Duration::from_secs(300);
+
+pub struct SimulationConfig {
+    pub seed: u64,
+    pub wave_num: i32,
+    pub cell_army: BTreeMap<i32, CellBundle>,
+    pub substances: Vec<SubstanceTemplate>,
+    pub wave_table: WaveTable,
+    pub infection_model: InfectionModel,
+    // length of a combat tick
+    pub timestep: Duration,
+    // time that passes between two app updates; a tick runs zero or more times per update
+    pub frame_time: Duration,
+    pub max_duration: Duration,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            wave_num: 0,
+            cell_army: BTreeMap::new(),
+            substances: vec![],
+            wave_table: WaveTable::default(),
+            infection_model: InfectionModel::default(),
+            timestep: SIMULATION_TIMESTEP,
+            frame_time: SIMULATION_FRAME_TIME,
+            max_duration: SIMULATION_MAX_DURATION,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+pub enum WaveResult {
+    Victory,
+    Defeat,
+    // neither side was wiped out before max_duration
+    Timeout,
+}
+
+#[derive(Debug)]
+pub struct WaveReport {
+    pub result: WaveResult,
+    pub elapsed: Duration,
+    pub surviving_cells: Vec<CellBundle>,
+    pub surviving_enemies: usize,
+    pub substances_dropped: usize,
+    pub combat_log: CombatLog,
+}
+
+pub fn simulate_wave(config: SimulationConfig) -> WaveReport {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+        .init_asset::<Mesh>()
+        .init_asset::<ColorMaterial>()
+        .init_asset::<AnimationClip>()
+        .init_asset::<Image>()
+        .insert_resource(TimeUpdateStrategy::ManualDuration(config.frame_time))
+        .insert_resource(Time::<Fixed>::from_duration(config.timestep))
+        .add_plugins(CombatLogPlugin)
+        .insert_resource(CombatLog::new(config.wave_num, Duration::ZERO))
+        .init_resource::<CellGrid>()
+        .init_resource::<Arena>()
+        .init_resource::<FlowField>()
+        .add_event::<CellConversionEvent>()
+        .insert_resource(GameRng::new(config.seed, config.wave_num))
+        .insert_resource(SubstanceResource(config.substances))
+        .insert_resource(WaveTableResource(config.wave_table))
+        .insert_resource(config.infection_model)
+        .insert_resource(PlayerResource {
+            good_cell_id: GoodCellId(config.cell_army.keys().max().map_or(0, |id| id + 1)),
+            cell_army: config.cell_army,
+            substance_collection: BTreeMap::new(),
+            loaded_substances: BTreeMap::new(),
+            fusion_bench: BTreeMap::new(),
+            wave_num: config.wave_num,
+            substance_id_gen: SubstanceIdGen(0),
+            seed: config.seed,
+            run_stats: RunStats::default(),
+        })
+        .add_systems(
+            Startup,
+            (
+                arena::generate_arena,
+                goodcell::spawn_good_cells,
+                badcell::spawn_bad_cells,
+            )
+                .chain(),
+        )
+        .add_systems(FixedUpdate, game::wave_systems());
+
+    let result = loop {
+        app.update();
+
+        let world = &mut app.world;
+        if world.query::<&GoodCell>().iter(world).next().is_none() {
+            break WaveResult::Defeat;
+        }
+        let bad_cells_left = world.query::<&BadCell>().iter(world).count();
+        let bosses_left = world.query::<&Boss>().iter(world).count();
+        if boss::is_wave_cleared(config.wave_num, bad_cells_left, bosses_left) {
+            break WaveResult::Victory;
+        }
+        if world.resource::<Time>().elapsed() >= config.max_duration {
+            break WaveResult::Timeout;
+        }
+    };
+
+    let world = &mut app.world;
+    let surviving_cells = world
+        .query::<(
+            &GoodCell,
+            &Transform,
+            &CellAttribute,
+            &CellStance,
+            &CellExperience,
+            &GoodCellClass,
+        )>()
+        .iter(world)
+        .map(
+            |(_, cell_trans, cell_attr, stance, experience, class)| CellBundle {
+                cell_trans: cell_trans.translation,
+                cell_attribute: cell_attr.clone(),
+                stance: *stance,
+                experience: experience.clone(),
+                class: *class,
+                // whatever is left of the effects wears off with the wave
+                status_effects: StatusEffects::default(),
+            },
+        )
+        .collect();
+    let surviving_enemies = world.query::<&BadCell>().iter(world).count();
+    let elapsed = world.resource::<Time>().elapsed();
+
+    // the wave ends here rather than in game_loop, so its event is logged directly
+    let mut combat_log = world.resource_mut::<CombatLog>();
+    combat_log.record(
+        elapsed,
+        CombatEvent::WaveEnded(WaveEnded {
+            wave_num: config.wave_num,
+            result,
+        }),
+    );
+    let combat_log = combat_log.clone();
+    let player_resources = world.resource::<PlayerResource>();
+
+    WaveReport {
+        result,
+        elapsed,
+        surviving_cells,
+        surviving_enemies,
+        substances_dropped: player_resources.substance_collection.len(),
+        combat_log,
+    }
+}
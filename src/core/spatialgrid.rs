@@ -0,0 +1,272 @@
+// Uniform grid over cell positions, so range queries only look at nearby cells
+// instead of every cell on the other side
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::npc::{badcell::BadCell, cell::Cell, goodcell::GoodCell};
+
+pub const SPATIAL_GRID_BUCKET_SIZE: f32 = 64.0;
+
+#[derive(Default)]
+pub struct SpatialGrid {
+    buckets: HashMap<IVec2, Vec<(Entity, Vec3)>>,
+}
+
+impl SpatialGrid {
+    pub fn clear(&mut self) {
+        self.buckets.clear();
+    }
+
+    pub fn insert(&mut self, entity: Entity, position: Vec3) {
+        self.buckets
+            .entry(Self::bucket(position.truncate()))
+            .or_default()
+            .push((entity, position));
+    }
+
+    // Every entry within radius of center
+    pub fn query_radius(
+        &self,
+        center: Vec3,
+        radius: f32,
+    ) -> impl Iterator<Item = (Entity, Vec3)> + '_ {
+        let min = Self::bucket(center.truncate() - Vec2::splat(radius));
+        let max = Self::bucket(center.truncate() + Vec2::splat(radius));
+
+        (min.x..=max.x)
+            .flat_map(move |x| (min.y..=max.y).map(move |y| IVec2::new(x, y)))
+            .filter_map(|key| self.buckets.get(&key))
+            .flatten()
+            .copied()
+            .filter(move |(_, position)| Vec3::distance(*position, center) <= radius)
+    }
+
+    pub fn nearest_in_radius(&self, center: Vec3, radius: f32) -> Option<(Entity, Vec3)> {
+        self.query_radius(center, radius).min_by(|(_, a), (_, b)| {
+            Vec3::distance(*a, center).total_cmp(&Vec3::distance(*b, center))
+        })
+    }
+
+    // The entry closest to center inside the box spanned by half_extents.
+    // Buckets are visited in rings around center, so the search stops as soon as
+    // nothing further out can be closer
+    pub fn nearest_in_box(&self, center: Vec3, half_extents: Vec2) -> Option<(Entity, Vec3)> {
+        let origin = Self::bucket(center.truncate());
+        let min = Self::bucket(center.truncate() - half_extents);
+        let max = Self::bucket(center.truncate() + half_extents);
+        let max_ring = (origin - min).max(max - origin).max_element();
+
+        let mut closest: Option<(Entity, Vec3)> = None;
+        let mut closest_distance = f32::MAX;
+
+        for ring in 0..=max_ring {
+            for key in Self::ring(origin, ring) {
+                if key.cmplt(min).any() || key.cmpgt(max).any() {
+                    continue;
+                }
+                let Some(bucket) = self.buckets.get(&key) else {
+                    continue;
+                };
+
+                for &(entity, position) in bucket {
+                    let offset = (position - center).truncate().abs();
+                    let distance = Vec3::distance(position, center);
+                    if offset.cmple(half_extents).all() && distance <= closest_distance {
+                        closest = Some((entity, position));
+                        closest_distance = distance;
+                    }
+                }
+            }
+
+            // anything in the next ring is at least this far away
+            if closest_distance <= ring as f32 * SPATIAL_GRID_BUCKET_SIZE {
+                break;
+            }
+        }
+
+        closest
+    }
+
+    fn bucket(position: Vec2) -> IVec2 {
+        (position / SPATIAL_GRID_BUCKET_SIZE).floor().as_ivec2()
+    }
+
+    fn ring(origin: IVec2, ring: i32) -> impl Iterator<Item = IVec2> {
+        let edges = (-ring..=ring).flat_map(move |x| {
+            [IVec2::new(x, -ring), IVec2::new(x, ring)]
+                .into_iter()
+                .take(if ring == 0 { 1 } else { 2 })
+        });
+        let sides =
+            (-ring + 1..ring).flat_map(move |y| [IVec2::new(-ring, y), IVec2::new(ring, y)]);
+
+        edges.chain(sides).map(move |offset| origin + offset)
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct CellGrid {
+    pub good_cells: SpatialGrid,
+    pub bad_cells: SpatialGrid,
+}
+
+// Rebuilt at the start of every combat frame from the cell transforms
+#[allow(clippy::type_complexity)]
+pub fn rebuild_cell_grid(
+    mut cell_grid: ResMut<CellGrid>,
+    goodcell_query: Query<(Entity, &Transform), (With<Cell>, With<GoodCell>)>,
+    badcell_query: Query<(Entity, &Transform), (With<Cell>, With<BadCell>)>,
+) {
+    cell_grid.good_cells.clear();
+    for (ent, cell_trans) in goodcell_query.iter() {
+        cell_grid.good_cells.insert(ent, cell_trans.translation);
+    }
+
+    cell_grid.bad_cells.clear();
+    for (ent, cell_trans) in badcell_query.iter() {
+        cell_grid.bad_cells.insert(ent, cell_trans.translation);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn grid(positions: &[Vec3]) -> SpatialGrid {
+        let mut grid = SpatialGrid::default();
+        for (idx, position) in positions.iter().enumerate() {
+            grid.insert(Entity::from_raw(idx as u32), *position);
+        }
+        grid
+    }
+
+    fn brute_force_in_box(positions: &[Vec3], center: Vec3, half_extents: Vec2) -> Option<f32> {
+        positions
+            .iter()
+            .filter(|position| {
+                (**position - center)
+                    .truncate()
+                    .abs()
+                    .cmple(half_extents)
+                    .all()
+            })
+            .map(|position| position.distance(center))
+            .min_by(|a, b| a.total_cmp(b))
+    }
+
+    fn brute_force_in_radius(positions: &[Vec3], center: Vec3, radius: f32) -> Vec<Vec3> {
+        positions
+            .iter()
+            .copied()
+            .filter(|position| position.distance(center) <= radius)
+            .collect()
+    }
+
+    fn distance_to(found: Option<(Entity, Vec3)>, center: Vec3) -> Option<f32> {
+        found.map(|(_, position)| position.distance(center))
+    }
+
+    #[test]
+    fn an_empty_grid_finds_nothing() {
+        let grid = SpatialGrid::default();
+        assert_eq!(grid.query_radius(Vec3::ZERO, 500.0).count(), 0);
+        assert_eq!(grid.nearest_in_radius(Vec3::ZERO, 500.0), None);
+        assert_eq!(grid.nearest_in_box(Vec3::ZERO, Vec2::splat(500.0)), None);
+    }
+
+    #[test]
+    fn a_far_ring_can_be_closer_than_a_near_corner() {
+        let center = Vec3::new(60.0, 1.0, 0.0);
+        // in the corner of the first ring, about 134 away
+        let corner = Vec3::new(-60.0, -60.0, 0.0);
+        // in the second ring along the axis, but only 70 away
+        let axis = Vec3::new(130.0, 1.0, 0.0);
+        let grid = grid(&[corner, axis]);
+
+        assert_eq!(
+            grid.nearest_in_box(center, Vec2::splat(200.0)),
+            Some((Entity::from_raw(1), axis))
+        );
+        assert_eq!(
+            grid.nearest_in_radius(center, 200.0),
+            Some((Entity::from_raw(1), axis))
+        );
+    }
+
+    #[test]
+    fn entries_just_outside_the_box_are_skipped() {
+        let center = Vec3::ZERO;
+        let half_extents = Vec2::new(100.0, 30.0);
+        let grid = grid(&[
+            Vec3::new(0.0, 30.5, 0.0),
+            Vec3::new(-100.5, 0.0, 0.0),
+            Vec3::new(99.0, -29.0, 0.0),
+        ]);
+
+        assert_eq!(
+            grid.nearest_in_box(center, half_extents),
+            Some((Entity::from_raw(2), Vec3::new(99.0, -29.0, 0.0)))
+        );
+        assert_eq!(grid.nearest_in_box(center, Vec2::new(98.5, 28.5)), None);
+    }
+
+    #[test]
+    fn entries_just_outside_the_radius_are_skipped() {
+        let grid = grid(&[Vec3::new(-64.0, 0.0, 0.0), Vec3::new(0.0, -64.5, 0.0)]);
+
+        let found: Vec<Vec3> = grid
+            .query_radius(Vec3::ZERO, 64.0)
+            .map(|(_, position)| position)
+            .collect();
+        assert_eq!(found, vec![Vec3::new(-64.0, 0.0, 0.0)]);
+    }
+
+    #[test]
+    fn queries_match_a_brute_force_scan() {
+        let mut rng = StdRng::seed_from_u64(9);
+        // negative and positive coordinates around the bucket boundaries
+        let positions: Vec<Vec3> = (0..300)
+            .map(|_| {
+                Vec3::new(
+                    rng.gen_range(-400.0..400.0),
+                    rng.gen_range(-400.0..400.0),
+                    0.0,
+                )
+            })
+            .collect();
+        let grid = grid(&positions);
+
+        for _ in 0..500 {
+            let center = Vec3::new(
+                rng.gen_range(-450.0..450.0),
+                rng.gen_range(-450.0..450.0),
+                0.0,
+            );
+            let half_extents = Vec2::new(rng.gen_range(1.0..300.0), rng.gen_range(1.0..300.0));
+            let radius = rng.gen_range(1.0..300.0);
+
+            assert_eq!(
+                distance_to(grid.nearest_in_box(center, half_extents), center),
+                brute_force_in_box(&positions, center, half_extents)
+            );
+
+            let mut expected = brute_force_in_radius(&positions, center, radius);
+            let mut found: Vec<Vec3> = grid
+                .query_radius(center, radius)
+                .map(|(_, position)| position)
+                .collect();
+            let by_position = |a: &Vec3, b: &Vec3| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y));
+            expected.sort_by(by_position);
+            found.sort_by(by_position);
+            assert_eq!(found, expected);
+
+            assert_eq!(
+                distance_to(grid.nearest_in_radius(center, radius), center),
+                expected
+                    .iter()
+                    .map(|position| position.distance(center))
+                    .min_by(|a, b| a.total_cmp(b))
+            );
+        }
+    }
+}
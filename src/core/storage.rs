@@ -0,0 +1,75 @@
+// Small key/value store that survives between sessions:
+// a file under the platform data dir on native, localStorage on the web
+use std::io;
+
+#[cfg(not(target_arch = "wasm32"))]
+const STORAGE_DIR_NAME: &str = "antidotes";
+
+#[cfg(not(target_arch = "wasm32"))]
+fn storage_path(key: &str) -> io::Result<std::path::PathBuf> {
+    let data_dir = dirs::data_dir().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "No data directory on this platform",
+        )
+    })?;
+    Ok(data_dir.join(STORAGE_DIR_NAME).join(format!("{key}.ron")))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read(key: &str) -> Option<String> {
+    std::fs::read_to_string(storage_path(key).ok()?).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn exists(key: &str) -> bool {
+    storage_path(key).is_ok_and(|path| path.exists())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write(key: &str, contents: &str) -> io::Result<()> {
+    let path = storage_path(key)?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, contents)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn remove(key: &str) -> io::Result<()> {
+    match std::fs::remove_file(storage_path(key)?) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> io::Result<web_sys::Storage> {
+    web_sys::window()
+        .and_then(|window| window.local_storage().ok().flatten())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "localStorage is not available"))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn read(key: &str) -> Option<String> {
+    local_storage().ok()?.get_item(key).ok().flatten()
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn exists(key: &str) -> bool {
+    local_storage().is_ok_and(|storage| matches!(storage.get_item(key), Ok(Some(_))))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn write(key: &str, contents: &str) -> io::Result<()> {
+    local_storage()?
+        .set_item(key, contents)
+        .map_err(|_| io::Error::new(io::ErrorKind::Other, "localStorage write failed"))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn remove(key: &str) -> io::Result<()> {
+    local_storage()?
+        .remove_item(key)
+        .map_err(|_| io::Error::new(io::ErrorKind::Other, "localStorage remove failed"))
+}
@@ -18,6 +18,7 @@ pub fn setup_resources(mut commands: Commands, asset_server: Res<AssetServer>) {
     commands.insert_resource(UiResource(button_click_sound));
 }
 
+#[allow(clippy::type_complexity)]
 pub fn button_systems(
     mut interaction_query: Query<
         (&Interaction, &mut BackgroundColor),
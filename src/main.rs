@@ -1,6 +1,9 @@
 use antidotes::{
-    core::{maincamera, states, userinterface},
-    plugins::{antidote, game, gameover, menu, playerresource, gameprepare, gamefinish},
+    core::{gamerng, maincamera, states, userinterface},
+    plugins::{
+        antidote, combatlog, game, gamefinish, gameover, gameprepare, menu, playerresource,
+        records, savegame, wave, wavestats,
+    },
 };
 use bevy::{prelude::*, asset::AssetMetaCheck};
 
@@ -10,6 +13,7 @@ fn main() {
         .add_plugins(DefaultPlugins)
         .add_event::<userinterface::ButtonClickEvent>()
         .add_state::<states::GameState>()
+        .init_resource::<gamerng::GameRng>()
         .add_systems(
             Startup,
             (maincamera::setup_camera, userinterface::setup_resources),
@@ -23,12 +27,17 @@ fn main() {
         )
         .add_plugins((
             antidote::AntidotePlugin,
+            wave::WavePlugin,
             playerresource::PlayerResourcePlugin,
             menu::MenuPlugin,
             gameprepare::GamePreparePlugin,
             game::GamePlugin,
             gamefinish::GameFinishPlugin,
             gameover::GameOverPlugin,
+            savegame::SaveGamePlugin,
+            combatlog::CombatLogPlugin,
+            wavestats::WaveStatsPlugin,
+            records::RecordsPlugin,
         ))
         .run();
 }
@@ -1,3 +1,7 @@
 pub mod cell;
 pub mod goodcell;
-pub mod badcell;
\ No newline at end of file
+pub mod badcell;
+pub mod experience;
+pub mod boss;
+pub mod infection;
+pub mod statuseffect;
@@ -1,192 +1,567 @@
-use std::time::Duration;
-
-use bevy::{
-    prelude::*,
-    sprite::{collide_aabb::collide, MaterialMesh2dBundle},
+use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    rngs::StdRng,
+    Rng,
 };
-use rand::Rng;
+use serde::{Deserialize, Serialize};
 
-use crate::plugins::{game::OnGameScreen, playerresource::PlayerResource};
+use crate::core::{
+    arena::Arena,
+    gamerng::GameRng,
+    navigation::{self, FlowField},
+    spatialgrid::CellGrid,
+};
+use crate::plugins::antidote::{SubstanceRarity, TargetAttribute};
+use crate::plugins::{
+    combatlog::{CellDamaged, CellInfected},
+    game::OnGameScreen,
+    playerresource::PlayerResource,
+    wave::WaveTableResource,
+};
 
 use super::{
+    boss::{is_boss_wave, spawn_boss},
     cell::{Cell, CellAttack, CellAttribute, Collider},
-    goodcell::GoodCell,
+    experience::CellExperience,
+    goodcell::{GoodCell, GOOD_CELL_SIZE},
+    infection::InfectionModel,
+    statuseffect::{StatusEffect, StatusEffectKind, StatusEffects},
 };
 
-// TODO: refactor constants to components or configs or resources instead?
+// Wave difficulty lives in the wave table (see plugins::wave)
 const BAD_CELL_SPAWN_RADIUS: f32 = 400.0;
-const BAD_CELL_SEARCH_RADIUS: f32 = 1000.0;
-const BAD_CELL_ATTACK_RANGE: f32 = 10.0;
-const BAD_CELL_BASE_COUNT: i32 = 25;
-const BAD_CELL_BASE_STRENGTH_MULTIPLIER: f32 = 0.25;
+const SPLITTER_SPLIT_COUNT: i32 = 2;
+// every bad cell rolls its speed once, before the archetype multiplier
+const BAD_CELL_MIN_SPEED: f32 = 30.0;
+const BAD_CELL_MAX_SPEED: f32 = 55.0;
 
 #[derive(Component)]
 pub struct BadCell;
+#[derive(Component, Default, Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum BadCellArchetype {
+    #[default]
+    Basic,
+    // fast and fragile, comes in numbers
+    Swarmer,
+    // slow and tanky
+    Blob,
+    // keeps its distance and infects from range
+    Spitter,
+    // divides into swarmers on death
+    Splitter,
+    // the boss of a boss wave, see npc::boss
+    PathogenCore,
+}
+
+impl BadCellArchetype {
+    pub fn health_multiplier(&self) -> f32 {
+        match self {
+            BadCellArchetype::Basic => 1.0,
+            BadCellArchetype::Swarmer => 0.5,
+            BadCellArchetype::Blob => 4.0,
+            BadCellArchetype::Spitter => 0.8,
+            BadCellArchetype::Splitter => 1.5,
+            BadCellArchetype::PathogenCore => 100.0,
+        }
+    }
+
+    pub fn damage_multiplier(&self) -> f32 {
+        match self {
+            BadCellArchetype::Basic => 1.0,
+            BadCellArchetype::Swarmer => 0.6,
+            BadCellArchetype::Blob => 1.5,
+            BadCellArchetype::Spitter => 0.3,
+            BadCellArchetype::Splitter => 1.0,
+            BadCellArchetype::PathogenCore => 3.0,
+        }
+    }
+
+    pub fn speed_multiplier(&self) -> f32 {
+        match self {
+            BadCellArchetype::Basic => 1.0,
+            BadCellArchetype::Swarmer => 1.8,
+            BadCellArchetype::Blob => 0.5,
+            BadCellArchetype::Spitter => 0.9,
+            BadCellArchetype::Splitter => 0.8,
+            BadCellArchetype::PathogenCore => 0.3,
+        }
+    }
+
+    // None means the wave's attack range is used
+    pub fn attack_range(&self) -> Option<f32> {
+        match self {
+            BadCellArchetype::Spitter => Some(80.0),
+            BadCellArchetype::PathogenCore => Some(40.0),
+            _ => None,
+        }
+    }
+
+    // the lowest tier of the substances this archetype drops
+    pub fn drop_rarity(&self) -> SubstanceRarity {
+        match self {
+            BadCellArchetype::PathogenCore => SubstanceRarity::Rare,
+            _ => SubstanceRarity::Common,
+        }
+    }
+
+    pub fn drop_chance(&self) -> i32 {
+        match self {
+            BadCellArchetype::Basic => 12,
+            BadCellArchetype::Swarmer => 6,
+            BadCellArchetype::Blob => 25,
+            BadCellArchetype::Spitter => 18,
+            BadCellArchetype::Splitter => 12,
+            BadCellArchetype::PathogenCore => 100,
+        }
+    }
+
+    // substances of this attribute drop twice as often from this archetype
+    pub fn loot_bias(&self) -> Option<TargetAttribute> {
+        match self {
+            BadCellArchetype::Basic => None,
+            BadCellArchetype::Swarmer => Some(TargetAttribute::Speed),
+            BadCellArchetype::Blob => Some(TargetAttribute::Health),
+            BadCellArchetype::Spitter => Some(TargetAttribute::Immune),
+            BadCellArchetype::Splitter => Some(TargetAttribute::Attack),
+            BadCellArchetype::PathogenCore => None,
+        }
+    }
+
+    // what a hit of this archetype leaves on the good cell
+    pub fn on_hit_effect(&self) -> Option<StatusEffect> {
+        match self {
+            BadCellArchetype::Blob => Some(StatusEffect::new(StatusEffectKind::Slow, 0.5, 3.0)),
+            BadCellArchetype::Spitter => {
+                Some(StatusEffect::new(StatusEffectKind::Poison, 1.5, 4.0))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn size(&self) -> f32 {
+        match self {
+            BadCellArchetype::Basic => 5.0,
+            BadCellArchetype::Swarmer => 3.5,
+            BadCellArchetype::Blob => 10.0,
+            BadCellArchetype::Spitter => 6.0,
+            BadCellArchetype::Splitter => 7.0,
+            BadCellArchetype::PathogenCore => 28.0,
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            BadCellArchetype::Basic => Color::RED,
+            BadCellArchetype::Swarmer => Color::ORANGE_RED,
+            BadCellArchetype::Blob => Color::MAROON,
+            BadCellArchetype::Spitter => Color::PURPLE,
+            BadCellArchetype::Splitter => Color::CRIMSON,
+            BadCellArchetype::PathogenCore => Color::rgb(0.45, 0.0, 0.2),
+        }
+    }
+
+    pub fn mesh(&self) -> Mesh {
+        let size = self.size();
+        match self {
+            BadCellArchetype::Swarmer => shape::RegularPolygon::new(size, 3).into(),
+            BadCellArchetype::Spitter => shape::RegularPolygon::new(size, 5).into(),
+            BadCellArchetype::Splitter => shape::RegularPolygon::new(size, 6).into(),
+            BadCellArchetype::PathogenCore => shape::RegularPolygon::new(size, 8).into(),
+            _ => shape::Circle::new(size).into(),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct MoveSpeed(pub f32);
+
+impl MoveSpeed {
+    pub fn roll(archetype: BadCellArchetype, rng: &mut StdRng) -> Self {
+        let speed = rng.gen_range(BAD_CELL_MIN_SPEED..=BAD_CELL_MAX_SPEED);
+        MoveSpeed(speed * archetype.speed_multiplier())
+    }
+}
+
 #[derive(Component)]
 pub struct SearchRange {
     pub range: f32,
 }
+#[derive(Component)]
+pub struct AttackRange {
+    pub range: f32,
+}
+// Carried by splitters, so the swarmers they divide into are as strong as their parent
+#[derive(Component, Clone, Copy)]
+pub struct SplitOnDeath {
+    pub strength: f32,
+    pub search_radius: f32,
+    pub attack_range: f32,
+}
+
+pub struct BadCellSpawn {
+    pub archetype: BadCellArchetype,
+    pub strength: f32,
+    pub position: Vec3,
+    pub search_radius: f32,
+    pub attack_range: f32,
+}
 
+#[allow(clippy::too_many_arguments)]
 pub fn spawn_bad_cells(
     mut commands: Commands,
+    mut game_rng: ResMut<GameRng>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     mut animations: ResMut<Assets<AnimationClip>>,
-    player_resource: ResMut<PlayerResource>
+    player_resource: ResMut<PlayerResource>,
+    wave_table: Res<WaveTableResource>,
+    arena: Res<Arena>,
 ) {
-    let current_wave = player_resource.wave_num;
-    let mut cell_count = 0;
-    let max_cell_count = BAD_CELL_BASE_COUNT + current_wave * 3;
-    let bad_cell_strength_multiplier = if current_wave != 0 && current_wave % 3 == 0 {
-        current_wave as f32 * BAD_CELL_BASE_STRENGTH_MULTIPLIER
+    let wave = wave_table.0.wave(player_resource.wave_num);
+    let boss_wave = is_boss_wave(player_resource.wave_num);
+    // the boss brings a smaller escort
+    let enemy_count = if boss_wave {
+        wave.enemy_count / 2
     } else {
-        1.0
+        wave.enemy_count
     };
+    let mut cell_count = 0;
+    let archetype_table =
+        WeightedIndex::new(wave.composition.iter().map(|(_, weight)| *weight)).ok();
 
-    while cell_count < max_cell_count {
-        let mut animation = AnimationClip::default();
-        let mut player = AnimationPlayer::default();
-        let mut origin_point = Vec3::new(0., 0., 0.);
-        origin_point.x =
-            rand::thread_rng().gen_range(-BAD_CELL_SPAWN_RADIUS..=BAD_CELL_SPAWN_RADIUS);
-        origin_point.y =
-            rand::thread_rng().gen_range(-BAD_CELL_SPAWN_RADIUS..=BAD_CELL_SPAWN_RADIUS);
-
-        let origin_point = origin_point.normalize() * rand::thread_rng().gen_range(300.0..=320.);
-
-        // TODO: refactor the below code
-        let anim_cell = Name::new(format!("anim_cell_{cell_count}"));
-        let child_origin = Vec3::new(0., 0., 0.);
-
-        let rand_keyframe_1 = Vec3::new(
-            rand::thread_rng().gen_range(-5.0..=5.0),
-            rand::thread_rng().gen_range(-5.0..=5.0),
-            0.0,
-        );
-        let rand_keyframe_2 = Vec3::new(
-            rand::thread_rng().gen_range(-5.0..=5.0),
-            rand::thread_rng().gen_range(-5.0..=5.0),
-            0.0,
-        );
-        let rand_keyframe_3 = Vec3::new(
-            rand::thread_rng().gen_range(-5.0..=5.0),
-            rand::thread_rng().gen_range(-5.0..=5.0),
-            0.0,
-        );
+    while cell_count < enemy_count {
+        let archetype = archetype_table
+            .as_ref()
+            .map_or_else(BadCellArchetype::default, |archetype_table| {
+                wave.composition[archetype_table.sample(&mut game_rng.spawn)].0
+            });
+        let origin_point = arena.spawn_point(&mut game_rng.spawn).unwrap_or_else(|| {
+            let mut origin_point = Vec3::new(0., 0., 0.);
+            origin_point.x = game_rng
+                .spawn
+                .gen_range(-BAD_CELL_SPAWN_RADIUS..=BAD_CELL_SPAWN_RADIUS);
+            origin_point.y = game_rng
+                .spawn
+                .gen_range(-BAD_CELL_SPAWN_RADIUS..=BAD_CELL_SPAWN_RADIUS);
+
+            origin_point.normalize()
+                * game_rng
+                    .spawn
+                    .gen_range(wave.spawn_ring_inner..=wave.spawn_ring_outer)
+        });
 
-        animation.add_curve_to_path(
-            EntityPath {
-                parts: vec![anim_cell.clone()],
+        spawn_bad_cell(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            &mut animations,
+            &mut game_rng.spawn,
+            BadCellSpawn {
+                archetype,
+                strength: wave.strength,
+                position: origin_point,
+                search_radius: wave.search_radius,
+                attack_range: wave.attack_range,
             },
-            VariableCurve {
-                keyframe_timestamps: vec![0.0, 1.0, 2.0, 3.0, 4.0],
-                keyframes: Keyframes::Translation(vec![
-                    child_origin,
-                    child_origin + rand_keyframe_1,
-                    child_origin + rand_keyframe_2,
-                    child_origin + rand_keyframe_3,
-                    // in case seamless looping is wanted, the last keyframe should
-                    // be the same as the first one
-                    child_origin,
-                ]),
+        );
+
+        cell_count += 1;
+    }
+
+    if boss_wave {
+        let origin_point = arena.spawn_point(&mut game_rng.spawn).unwrap_or_else(|| {
+            let direction = Vec3::new(
+                game_rng.spawn.gen_range(-1.0..=1.0),
+                game_rng.spawn.gen_range(-1.0..=1.0),
+                0.0,
+            );
+            direction.normalize_or_zero() * wave.spawn_ring_outer
+        });
+        spawn_boss(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            &mut animations,
+            &mut game_rng.spawn,
+            BadCellSpawn {
+                archetype: BadCellArchetype::PathogenCore,
+                strength: wave.strength,
+                position: origin_point,
+                search_radius: wave.search_radius,
+                attack_range: wave.attack_range,
             },
         );
+    }
+}
 
-        player.play(animations.add(animation)).repeat();
-
-        let modify_speed = 5.7 - bad_cell_strength_multiplier * 0.7;
-        let modify_damage = 0.8 + bad_cell_strength_multiplier * 0.9;
-
-        // TODO: clean up unused components!!!
-        commands
-            .spawn((
-                SpatialBundle::from_transform(Transform::from_translation(origin_point)),
-                Cell,
-                BadCell,
-                CellAttribute {
-                    health: 10.0 * bad_cell_strength_multiplier,
-                    immune: 100.0,
-                    infection: 0.0,
-                    cell_attack: CellAttack::new(modify_speed, modify_damage),
-                },
-                Collider,
-                SearchRange {
-                    range: BAD_CELL_SEARCH_RADIUS,
-                },
-                OnGameScreen, // TODO: find a better way to add this component to a cell
-            ))
-            .with_children(|child_builder| {
-                child_builder.spawn((
-                    MaterialMesh2dBundle {
-                        mesh: meshes.add(shape::Circle::new(5.).into()).into(),
-                        material: materials.add(ColorMaterial::from(Color::RED)),
-                        transform: Transform::from_translation(child_origin),
-                        ..default()
-                    },
-                    anim_cell,
-                    player,
-                ));
-            });
+// The stats of a freshly spawned bad cell
+pub fn bad_cell_attribute(archetype: BadCellArchetype, strength: f32) -> CellAttribute {
+    let modify_speed = 5.7 - strength * 0.7;
+    let modify_damage = (0.8 + strength * 0.9) * archetype.damage_multiplier();
 
-        cell_count += 1;
+    CellAttribute {
+        health: 10.0 * strength * archetype.health_multiplier(),
+        immune: 100.0,
+        infection: 0.0,
+        cure: 0.0,
+        infection_proof: false,
+        cell_attack: CellAttack::new(modify_speed, modify_damage),
     }
 }
 
+pub fn spawn_bad_cell(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    animations: &mut Assets<AnimationClip>,
+    rng: &mut StdRng,
+    bad_cell_spawn: BadCellSpawn,
+) -> Entity {
+    let archetype = bad_cell_spawn.archetype;
+    let bad_cell_strength_multiplier = bad_cell_spawn.strength;
+    let move_speed = MoveSpeed::roll(archetype, rng);
+
+    // TODO: clean up unused components!!!
+    let mut bad_cell_commands = commands.spawn((
+        SpatialBundle::from_transform(Transform::from_translation(bad_cell_spawn.position)),
+        Cell,
+        BadCell,
+        archetype,
+        bad_cell_attribute(archetype, bad_cell_strength_multiplier),
+        move_speed,
+        StatusEffects::default(),
+        Collider,
+        SearchRange {
+            range: bad_cell_spawn.search_radius,
+        },
+        AttackRange {
+            range: archetype
+                .attack_range()
+                .unwrap_or(bad_cell_spawn.attack_range),
+        },
+        OnGameScreen, // TODO: find a better way to add this component to a cell
+    ));
+    bad_cell_commands.with_children(|child_builder| {
+        spawn_bad_cell_mesh(child_builder, meshes, materials, animations, rng, archetype);
+    });
+
+    if archetype == BadCellArchetype::Splitter {
+        bad_cell_commands.insert(SplitOnDeath {
+            strength: bad_cell_strength_multiplier,
+            search_radius: bad_cell_spawn.search_radius,
+            attack_range: bad_cell_spawn.attack_range,
+        });
+    }
+
+    bad_cell_commands.id()
+}
+
+// The wobbling mesh of a bad cell, spawned as a child so the cell itself can move
+pub fn spawn_bad_cell_mesh(
+    child_builder: &mut ChildBuilder,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    animations: &mut Assets<AnimationClip>,
+    rng: &mut StdRng,
+    archetype: BadCellArchetype,
+) {
+    let mut animation = AnimationClip::default();
+    let mut player = AnimationPlayer::default();
+
+    // TODO: refactor the below code
+    let anim_cell = Name::new("anim_cell");
+    let child_origin = Vec3::new(0., 0., 0.);
+
+    let rand_keyframe_1 = Vec3::new(rng.gen_range(-5.0..=5.0), rng.gen_range(-5.0..=5.0), 0.0);
+    let rand_keyframe_2 = Vec3::new(rng.gen_range(-5.0..=5.0), rng.gen_range(-5.0..=5.0), 0.0);
+    let rand_keyframe_3 = Vec3::new(rng.gen_range(-5.0..=5.0), rng.gen_range(-5.0..=5.0), 0.0);
+
+    animation.add_curve_to_path(
+        EntityPath {
+            parts: vec![anim_cell.clone()],
+        },
+        VariableCurve {
+            keyframe_timestamps: vec![0.0, 1.0, 2.0, 3.0, 4.0],
+            keyframes: Keyframes::Translation(vec![
+                child_origin,
+                child_origin + rand_keyframe_1,
+                child_origin + rand_keyframe_2,
+                child_origin + rand_keyframe_3,
+                // in case seamless looping is wanted, the last keyframe should
+                // be the same as the first one
+                child_origin,
+            ]),
+        },
+    );
+
+    player.play(animations.add(animation)).repeat();
+
+    child_builder.spawn((
+        MaterialMesh2dBundle {
+            mesh: meshes.add(archetype.mesh()).into(),
+            material: materials.add(ColorMaterial::from(archetype.color())),
+            transform: Transform::from_translation(child_origin),
+            ..default()
+        },
+        anim_cell,
+        player,
+    ));
+}
+
+// A dead splitter leaves swarmers behind at its position
+pub fn split_bad_cell(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    animations: &mut Assets<AnimationClip>,
+    rng: &mut StdRng,
+    position: Vec3,
+    split_on_death: &SplitOnDeath,
+) {
+    for _ in 0..SPLITTER_SPLIT_COUNT {
+        let offset = Vec3::new(rng.gen_range(-8.0..=8.0), rng.gen_range(-8.0..=8.0), 0.0);
+        spawn_bad_cell(
+            commands,
+            meshes,
+            materials,
+            animations,
+            rng,
+            BadCellSpawn {
+                archetype: BadCellArchetype::Swarmer,
+                strength: split_on_death.strength,
+                position: position + offset,
+                search_radius: split_on_death.search_radius,
+                attack_range: split_on_death.attack_range,
+            },
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn move_attack(
-    time: ResMut<Time>,
+    time: Res<Time>,
+    cell_grid: Res<CellGrid>,
+    flow_field: Res<FlowField>,
+    arena: Res<Arena>,
+    infection_model: Res<InfectionModel>,
+    mut game_rng: ResMut<GameRng>,
+    mut damaged_events: EventWriter<CellDamaged>,
+    mut infected_events: EventWriter<CellInfected>,
     mut badcell_query: Query<
-        (&mut Transform, &SearchRange, &mut CellAttribute),
+        (
+            Entity,
+            &mut Transform,
+            &SearchRange,
+            &AttackRange,
+            &BadCellArchetype,
+            &MoveSpeed,
+            &mut CellAttribute,
+            &StatusEffects,
+        ),
         (With<BadCell>, With<OnGameScreen>),
     >,
     mut collision_query: Query<
-        (&Transform, &GoodCell, &mut CellAttribute),
-        (With<Collider>, Without<BadCell>),
+        (&mut CellAttribute, &CellExperience, &mut StatusEffects),
+        (With<Collider>, With<GoodCell>, Without<BadCell>),
     >,
 ) {
-    let target_pos = Vec3::new(0., 0., 0.);
-    for (mut bad_cell_trans, cell_search_range, mut badcell_attr) in badcell_query.iter_mut() {
-        let mut direction = (target_pos - bad_cell_trans.translation).normalize();
-        let rand_speed = rand::thread_rng().gen_range(5.0..=80.0);
-        let mut closest_distance_to_good_cell: f32 = f32::MAX;
-
-        for (good_cell_trans, good_cell, mut goodcell_attr) in collision_query.iter_mut() {
-            if Vec3::distance(good_cell_trans.translation, bad_cell_trans.translation)
-                <= BAD_CELL_ATTACK_RANGE
-            {
-                let attack_rate = badcell_attr.cell_attack.attack_rate;
-                badcell_attr
-                    .cell_attack
-                    .timer
-                    .tick(Duration::from_secs_f32(attack_rate));
-                if badcell_attr.cell_attack.timer.finished() {
-                    let damage = badcell_attr.cell_attack.damage;
-                    goodcell_attr.inflict_dmg(damage);
-
-                    // TODO: refactor this
-                    let infect_proc_chance = rand::thread_rng().gen_range(1..=100);
-                    if infect_proc_chance <= 5 {
-                        goodcell_attr.infect(2.0);
-                    }
+    for (
+        bad_cell_ent,
+        mut bad_cell_trans,
+        cell_search_range,
+        cell_attack_range,
+        archetype,
+        move_speed,
+        mut badcell_attr,
+        badcell_status,
+    ) in badcell_query.iter_mut()
+    {
+        // every good cell in range is hit, apart from the ones already killed this tick
+        let targets: Vec<Entity> = cell_grid
+            .good_cells
+            .query_radius(bad_cell_trans.translation, cell_attack_range.range)
+            .map(|(good_cell_ent, _)| good_cell_ent)
+            .filter(|good_cell_ent| {
+                collision_query
+                    .get(*good_cell_ent)
+                    .is_ok_and(|(goodcell_attr, _, _)| goodcell_attr.health > 0.0)
+            })
+            .collect();
+        let has_target_in_range = !targets.is_empty();
+
+        let attacks = if has_target_in_range {
+            badcell_attr
+                .cell_attack
+                .tick(time.delta().div_f32(badcell_status.slow_factor()))
+        } else {
+            0
+        };
+        for good_cell_ent in targets {
+            let Ok((mut goodcell_attr, experience, mut goodcell_status)) =
+                collision_query.get_mut(good_cell_ent)
+            else {
+                continue;
+            };
+            for _ in 0..attacks {
+                if goodcell_attr.health <= 0.0 {
+                    break;
                 }
-            } else {
-                let maybe_collide = collide(
-                    bad_cell_trans.translation,
-                    Vec2::new(cell_search_range.range, cell_search_range.range),
-                    good_cell_trans.translation,
-                    Vec2::new(good_cell.cell_size, good_cell.cell_size),
-                );
-
-                let distance =
-                    Vec3::distance(good_cell_trans.translation, bad_cell_trans.translation);
-
-                if maybe_collide.is_some() && distance <= closest_distance_to_good_cell {
-                    direction =
-                        (good_cell_trans.translation - bad_cell_trans.translation).normalize();
-                    closest_distance_to_good_cell = distance;
+                let damage = goodcell_status.absorb(badcell_attr.cell_attack.damage);
+                goodcell_attr.inflict_dmg(damage);
+                if let Some(effect) = archetype.on_hit_effect() {
+                    goodcell_status.apply(effect);
+                }
+                damaged_events.send(CellDamaged {
+                    entity: good_cell_ent,
+                    attacker: bad_cell_ent,
+                    amount: damage,
+                    health: goodcell_attr.health,
+                });
+
+                let infection = infection_model.hit_infection(*archetype, &mut game_rng.combat)
+                    * experience.infection_factor();
+                if infection > 0.0 && !goodcell_attr.infection_proof {
+                    goodcell_attr.infect(infection);
+                    infected_events.send(CellInfected {
+                        entity: good_cell_ent,
+                        attacker: bad_cell_ent,
+                        amount: infection,
+                        infection: goodcell_attr.infection,
+                    });
                 }
             }
         }
 
-        bad_cell_trans.translation += direction * rand_speed * time.delta_seconds();
+        // spitters stop to shoot as soon as something is in range
+        if *archetype == BadCellArchetype::Spitter && has_target_in_range {
+            continue;
+        }
+
+        // head for the closest good cell in the search box, same as the old aabb check,
+        // following the flow field around the walls. Without a target they follow the field
+        // to the closest good cell anywhere, or go for the centre when there is none
+        let position = bad_cell_trans.translation;
+        let search_half_extents = Vec2::splat((cell_search_range.range + GOOD_CELL_SIZE) / 2.0);
+        let desired = match cell_grid
+            .good_cells
+            .nearest_in_box(position, search_half_extents)
+        {
+            Some((_, good_cell_pos)) => flow_field
+                .towards_good_cells(position.truncate())
+                .unwrap_or_else(|| (good_cell_pos - position).truncate().normalize_or_zero()),
+            None => flow_field
+                .towards_good_cells(position.truncate())
+                .unwrap_or_else(|| (-position.truncate()).normalize_or_zero()),
+        };
+        let direction = navigation::steer(
+            position,
+            desired,
+            &cell_grid.bad_cells,
+            &arena,
+            archetype.size(),
+        );
+
+        bad_cell_trans.translation += (direction * move_speed.0 * time.delta_seconds()).extend(0.0);
     }
 }
@@ -0,0 +1,275 @@
+// Every few waves a Pathogen Core leads the attack. It gets more dangerous as it loses
+// health, keeps spawning minions and infects the good cells around it
+use std::{fmt, time::Duration};
+
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng};
+
+use crate::core::{gamerng::GameRng, spatialgrid::CellGrid};
+
+use super::{
+    badcell::{bad_cell_attribute, spawn_bad_cell, BadCell, BadCellArchetype, BadCellSpawn},
+    cell::CellAttribute,
+    experience::CellExperience,
+    goodcell::GoodCell,
+};
+
+pub const BOSS_WAVE_INTERVAL: i32 = 5;
+// minions appear this far from the centre of the boss
+const MINION_SPAWN_DISTANCE: f32 = 40.0;
+const AURA_COLOR: Color = Color::rgba(0.8, 0.1, 0.2, 0.6);
+
+pub fn is_boss_wave(wave_num: i32) -> bool {
+    (wave_num + 1) % BOSS_WAVE_INTERVAL == 0
+}
+
+// A boss wave is won once the boss is dead, whatever is left of its escort does not matter
+pub fn is_wave_cleared(wave_num: i32, bad_cells_left: usize, bosses_left: usize) -> bool {
+    if is_boss_wave(wave_num) {
+        bosses_left == 0
+    } else {
+        bad_cells_left == 0
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BossPhase {
+    Awakened,
+    // below two thirds of its health
+    Frenzied,
+    // below a third of its health
+    Enraged,
+}
+
+impl BossPhase {
+    pub fn from_health_ratio(health_ratio: f32) -> Self {
+        if health_ratio > 2.0 / 3.0 {
+            BossPhase::Awakened
+        } else if health_ratio > 1.0 / 3.0 {
+            BossPhase::Frenzied
+        } else {
+            BossPhase::Enraged
+        }
+    }
+
+    // seconds between two minion spawns
+    pub fn minion_interval(&self) -> f32 {
+        match self {
+            BossPhase::Awakened => 8.0,
+            BossPhase::Frenzied => 5.0,
+            BossPhase::Enraged => 3.0,
+        }
+    }
+
+    pub fn minion_count(&self) -> i32 {
+        match self {
+            BossPhase::Awakened => 1,
+            BossPhase::Frenzied => 2,
+            BossPhase::Enraged => 3,
+        }
+    }
+
+    pub fn aura_radius(&self) -> f32 {
+        match self {
+            BossPhase::Awakened => 60.0,
+            BossPhase::Frenzied => 80.0,
+            BossPhase::Enraged => 100.0,
+        }
+    }
+
+    // infection per second on every good cell inside the aura
+    pub fn aura_infection(&self) -> f32 {
+        match self {
+            BossPhase::Awakened => 0.5,
+            BossPhase::Frenzied => 1.0,
+            BossPhase::Enraged => 2.0,
+        }
+    }
+
+    pub fn attack_rate_multiplier(&self) -> f32 {
+        match self {
+            BossPhase::Awakened => 1.0,
+            BossPhase::Frenzied => 0.8,
+            BossPhase::Enraged => 0.5,
+        }
+    }
+}
+
+impl fmt::Display for BossPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BossPhase::Awakened => write!(f, "Awakened"),
+            BossPhase::Frenzied => write!(f, "Frenzied"),
+            BossPhase::Enraged => write!(f, "Enraged"),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Boss {
+    pub max_health: f32,
+    pub phase: BossPhase,
+    base_attack_rate: f32,
+    minion_timer: Timer,
+    // minions are spawned like the boss' escort
+    minion_spawn: BadCellSpawn,
+}
+
+impl Boss {
+    pub fn health_ratio(&self, cell_attr: &CellAttribute) -> f32 {
+        f32::max(0.0, cell_attr.health / self.max_health)
+    }
+}
+
+// Spawned by a boss, they drop no substances so the boss can not be farmed
+#[derive(Component)]
+pub struct Minion;
+
+pub fn spawn_boss(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    animations: &mut Assets<AnimationClip>,
+    rng: &mut StdRng,
+    boss_spawn: BadCellSpawn,
+) {
+    let minion_spawn = BadCellSpawn {
+        archetype: BadCellArchetype::Swarmer,
+        ..boss_spawn
+    };
+    let cell_attr = bad_cell_attribute(boss_spawn.archetype, boss_spawn.strength);
+    let phase = BossPhase::Awakened;
+    let boss = Boss {
+        max_health: cell_attr.health,
+        phase,
+        base_attack_rate: cell_attr.cell_attack.attack_rate,
+        minion_timer: Timer::from_seconds(phase.minion_interval(), TimerMode::Repeating),
+        minion_spawn,
+    };
+
+    let boss_ent = spawn_bad_cell(commands, meshes, materials, animations, rng, boss_spawn);
+    commands.entity(boss_ent).insert(boss);
+}
+
+// Moves the boss to the phase that matches its health and spawns its minions
+pub fn update_boss(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut game_rng: ResMut<GameRng>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut animations: ResMut<Assets<AnimationClip>>,
+    mut boss_query: Query<(&Transform, &mut CellAttribute, &mut Boss), With<BadCell>>,
+) {
+    for (boss_trans, mut cell_attr, mut boss) in boss_query.iter_mut() {
+        if cell_attr.health <= 0.0 {
+            continue;
+        }
+
+        let phase = BossPhase::from_health_ratio(boss.health_ratio(&cell_attr));
+        if phase != boss.phase {
+            boss.phase = phase;
+            cell_attr.cell_attack.attack_rate =
+                boss.base_attack_rate * phase.attack_rate_multiplier();
+            boss.minion_timer
+                .set_duration(Duration::from_secs_f32(phase.minion_interval()));
+        }
+
+        let waves = boss
+            .minion_timer
+            .tick(time.delta())
+            .times_finished_this_tick();
+        for _ in 0..waves * phase.minion_count() as u32 {
+            let angle = game_rng.spawn.gen_range(0.0..std::f32::consts::TAU);
+            let offset = Vec3::new(angle.cos(), angle.sin(), 0.0) * MINION_SPAWN_DISTANCE;
+            let minion_ent = spawn_bad_cell(
+                &mut commands,
+                &mut meshes,
+                &mut materials,
+                &mut animations,
+                &mut game_rng.spawn,
+                BadCellSpawn {
+                    position: boss_trans.translation + offset,
+                    ..boss.minion_spawn
+                },
+            );
+            commands.entity(minion_ent).insert(Minion);
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn infection_aura(
+    time: Res<Time>,
+    cell_grid: Res<CellGrid>,
+    boss_query: Query<(&Transform, &CellAttribute, &Boss), (With<BadCell>, Without<GoodCell>)>,
+    mut goodcell_query: Query<(&mut CellAttribute, &CellExperience), With<GoodCell>>,
+) {
+    for (boss_trans, boss_attr, boss) in boss_query.iter() {
+        if boss_attr.health <= 0.0 {
+            continue;
+        }
+
+        let infection = boss.phase.aura_infection() * time.delta_seconds();
+        for (good_cell_ent, _) in cell_grid
+            .good_cells
+            .query_radius(boss_trans.translation, boss.phase.aura_radius())
+        {
+            if let Ok((mut cell_attr, experience)) = goodcell_query.get_mut(good_cell_ent) {
+                cell_attr.infect(infection * experience.infection_factor());
+            }
+        }
+    }
+}
+
+pub fn draw_boss_aura(mut gizmos: Gizmos, boss_query: Query<(&Transform, &Boss), With<BadCell>>) {
+    for (boss_trans, boss) in boss_query.iter() {
+        gizmos.circle_2d(
+            boss_trans.translation.truncate(),
+            boss.phase.aura_radius(),
+            AURA_COLOR,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_fifth_wave_is_a_boss_wave() {
+        let boss_waves: Vec<i32> = (0..15).filter(|wave_num| is_boss_wave(*wave_num)).collect();
+        assert_eq!(boss_waves, vec![4, 9, 14]);
+    }
+
+    #[test]
+    fn a_boss_wave_is_won_once_the_boss_is_dead() {
+        assert!(!is_wave_cleared(4, 0, 1));
+        assert!(is_wave_cleared(4, 3, 0));
+        assert!(!is_wave_cleared(3, 3, 0));
+        assert!(is_wave_cleared(3, 0, 0));
+    }
+
+    #[test]
+    fn the_phase_follows_the_health_thirds() {
+        assert_eq!(BossPhase::from_health_ratio(1.0), BossPhase::Awakened);
+        assert_eq!(BossPhase::from_health_ratio(0.7), BossPhase::Awakened);
+        assert_eq!(BossPhase::from_health_ratio(2.0 / 3.0), BossPhase::Frenzied);
+        assert_eq!(BossPhase::from_health_ratio(0.4), BossPhase::Frenzied);
+        assert_eq!(BossPhase::from_health_ratio(1.0 / 3.0), BossPhase::Enraged);
+        assert_eq!(BossPhase::from_health_ratio(0.0), BossPhase::Enraged);
+    }
+
+    #[test]
+    fn later_phases_are_more_dangerous() {
+        let phases = [BossPhase::Awakened, BossPhase::Frenzied, BossPhase::Enraged];
+        for pair in phases.windows(2) {
+            let (calmer, angrier) = (pair[0], pair[1]);
+            assert!(angrier.minion_interval() < calmer.minion_interval());
+            assert!(angrier.minion_count() > calmer.minion_count());
+            assert!(angrier.aura_radius() > calmer.aura_radius());
+            assert!(angrier.aura_infection() > calmer.aura_infection());
+            assert!(angrier.attack_rate_multiplier() < calmer.attack_rate_multiplier());
+        }
+    }
+}
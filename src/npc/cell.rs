@@ -1,38 +1,90 @@
+use std::time::Duration;
+
 use bevy::prelude::*;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
+use crate::core::gamerng::GameRng;
 use crate::plugins::{
-    antidote::{SubstanceResource, SubstanceType, TargetAttribute},
+    antidote::{roll_drop, SubstanceResource, SubstanceType, TargetAttribute},
+    combatlog::{CellConverted, CellKilled, CellRecovered, SubstanceDropped},
     game::{CollectedSubstanceDisplay, OnGameScreen},
     playerresource::PlayerResource,
+    wave::WaveTableResource,
+};
+
+use super::{
+    badcell::{
+        spawn_bad_cell_mesh, split_bad_cell, AttackRange, BadCell, BadCellArchetype, MoveSpeed,
+        SearchRange, SplitOnDeath,
+    },
+    boss::{Boss, Minion},
+    experience::{CellExperience, HealthCap},
+    goodcell::{
+        spawn_good_cell_mesh, CellStance, FormationSlot, GoodCell, GoodCellClass, SlotPath,
+    },
+    infection::InfectionModel,
+    statuseffect::StatusEffects,
 };
 
-use super::{badcell::BadCell, goodcell::GoodCell};
+// Speegen can push the cooldown down to (or below) zero
+const MIN_ATTACK_COOLDOWN: f32 = 0.1;
 
 #[derive(Component)]
 pub struct Collider;
 
-#[derive(Component, Clone)]
+#[derive(Component, Clone, Debug, Serialize, Deserialize)]
 pub struct CellBundle {
     pub cell_trans: Vec3,
     pub cell_attribute: CellAttribute,
+    // saves from before stances existed hold position
+    #[serde(default)]
+    pub stance: CellStance,
+    #[serde(default)]
+    pub experience: CellExperience,
+    #[serde(default)]
+    pub class: GoodCellClass,
+    // effects the cell starts its next wave with
+    #[serde(default)]
+    pub status_effects: StatusEffects,
 }
 
-#[derive(Component, Clone)]
+#[derive(Component, Clone, Debug, Serialize, Deserialize)]
 pub struct CellAttribute {
     pub health: f32,
     pub immune: f32,
     pub cell_attack: CellAttack,
     pub infection: f32,
+    // bad cells at or below this much health are cured instead of hit
+    #[serde(default)]
+    pub cure: f32,
+    // set by a legendary reagent, infection never sticks to these cells
+    #[serde(default)]
+    pub infection_proof: bool,
 }
 
 impl CellAttribute {
+    // A freshly brewed or cured cell
+    pub fn new_recruit() -> Self {
+        Self {
+            health: 50.0,
+            cell_attack: CellAttack::new(0.5, 20.0),
+            immune: 30.0,
+            infection: 0.0,
+            cure: 0.0,
+            infection_proof: false,
+        }
+    }
+
     pub fn inflict_dmg(&mut self, damage: f32) {
         self.health -= damage;
         self.health = f32::max(0.0, self.health);
     }
 
     pub fn infect(&mut self, rate: f32) {
+        if self.infection_proof {
+            return;
+        }
         self.infection += rate;
     }
 }
@@ -40,7 +92,7 @@ impl CellAttribute {
 #[derive(Component)]
 pub struct Cell;
 
-#[derive(Component, Clone)]
+#[derive(Component, Clone, Debug, Serialize, Deserialize)]
 pub struct CellAttack {
     pub attack_rate: f32, // as seconds
     pub damage: f32,
@@ -55,66 +107,109 @@ impl CellAttack {
             timer: Timer::from_seconds(attack_rate, TimerMode::Repeating),
         }
     }
+
+    // Advances the cooldown by the elapsed time and returns how many attacks are ready.
+    // The timer follows attack_rate, so a brewed cooldown change applies straight away
+    pub fn tick(&mut self, delta: Duration) -> u32 {
+        let cooldown = Duration::from_secs_f32(f32::max(self.attack_rate, MIN_ATTACK_COOLDOWN));
+        if self.timer.duration() != cooldown {
+            self.timer.set_duration(cooldown);
+        }
+
+        self.timer.tick(delta).times_finished_this_tick()
+    }
 }
 
-//TODO: find a better way to handle a cell being destroyed as we can mutate the cell instead
-// for example: making bad cell turn "good" and vice versa
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+pub enum CellConversion {
+    // a good cell lost to its infection and now fights for the enemy
+    Infected,
+    // a weakened bad cell was cured and joins the army
+    Cured,
+}
+
+#[derive(Event)]
+pub struct CellConversionEvent {
+    pub entity: Entity,
+    pub conversion: CellConversion,
+}
+
+// Cells that change sides are mutated in convert_cells, this only handles dead cells
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn destroy_cell(
     substance_resources: Res<SubstanceResource>,
     asset_server: Res<AssetServer>,
     mut commands: Commands,
+    mut game_rng: ResMut<GameRng>,
     mut player_resources: ResMut<PlayerResource>,
+    mut killed_events: EventWriter<CellKilled>,
+    mut dropped_events: EventWriter<SubstanceDropped>,
     mut query: Query<
         (
             Entity,
             &Transform,
             &CellAttribute,
             Option<&BadCell>,
+            Option<&BadCellArchetype>,
+            Option<&SplitOnDeath>,
+            Option<&Minion>,
             Option<&GoodCell>,
         ),
         With<Cell>,
     >,
     mut collected_sub_display_query: Query<(&Transform, &mut Text, &mut CollectedSubstanceDisplay)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
     mut animations: ResMut<Assets<AnimationClip>>,
 ) {
-    let (collected_sub_display_trans, mut text_2d, mut collected_count) =
-        collected_sub_display_query.single_mut();
-    let sub_attack_img: Handle<Image> = asset_server.load("sprites/sub_attack.png");
-    let sub_speed_img: Handle<Image> = asset_server.load("sprites/sub_speed.png");
-    let sub_immune_img: Handle<Image> = asset_server.load("sprites/sub_immune.png");
-    let sub_health_img: Handle<Image> = asset_server.load("sprites/sub_health.png");
-
-    for (ent, cell_trans, cell_attr, maybe_badcell, maybe_goodcell) in query.iter_mut() {
+    for (
+        ent,
+        cell_trans,
+        cell_attr,
+        maybe_badcell,
+        maybe_archetype,
+        maybe_split_on_death,
+        maybe_minion,
+        maybe_goodcell,
+    ) in query.iter_mut()
+    {
         if cell_attr.health <= 0.0 {
             if maybe_badcell.is_some() {
-                let drop_chance = rand::thread_rng().gen_range(1..=100);
-                if drop_chance <= 12 {
-                    let resource_len = substance_resources.0.len();
-                    let random_substance_idx = rand::thread_rng().gen_range(0..resource_len);
-
-                    let mut random_substance = substance_resources.0[random_substance_idx].clone();
-                    random_substance.id = player_resources.substance_id_gen.0;
-                    random_substance.value = match random_substance.target_attribute {
-                        TargetAttribute::Speed => rand::thread_rng().gen_range(-0.4..=0.2),
-                        _ => rand::thread_rng().gen_range(-5.0..=8.0),
-                    };
-                    random_substance.substance_type = if random_substance.value < 0.0 {
-                        SubstanceType::Bitter
-                    } else if f32::eq(&random_substance.value, &0.0) {
-                        SubstanceType::Balanced
+                // cells without an archetype drop like basic ones
+                let archetype = maybe_archetype.copied().unwrap_or_default();
+
+                if let Some(split_on_death) = maybe_split_on_death {
+                    split_bad_cell(
+                        &mut commands,
+                        &mut meshes,
+                        &mut materials,
+                        &mut animations,
+                        &mut game_rng.spawn,
+                        cell_trans.translation,
+                        split_on_death,
+                    );
+                }
+
+                let drop_chance = game_rng.loot.gen_range(1..=100);
+                let maybe_substance =
+                    if drop_chance <= archetype.drop_chance() && maybe_minion.is_none() {
+                        roll_drop(
+                            &substance_resources.0,
+                            archetype.loot_bias(),
+                            player_resources.substance_id_gen.0,
+                            archetype.drop_rarity(),
+                            &mut game_rng.loot,
+                        )
                     } else {
-                        SubstanceType::Sweet
+                        None
                     };
+                if let Some(mut random_substance) = maybe_substance {
                     player_resources
                         .substance_collection
                         .insert(random_substance.id, random_substance.clone());
                     player_resources.substance_id_gen.0 += 1;
 
-                    if random_substance.value < 0.0
-                        && random_substance.target_attribute == TargetAttribute::Immune
-                    {
-                        random_substance.value *= -1.0;
-                    } else if random_substance.value > 0.0
+                    if random_substance.value != 0.0
                         && random_substance.target_attribute == TargetAttribute::Immune
                     {
                         random_substance.value *= -1.0;
@@ -124,110 +219,443 @@ pub fn destroy_cell(
                         random_substance.substance_type = SubstanceType::Sweet
                     }
 
-                    let drop_chance_balanced = rand::thread_rng().gen_range(1..=100);
+                    let drop_chance_balanced = game_rng.loot.gen_range(1..=100);
                     if drop_chance_balanced < 10 {
                         random_substance.substance_type = SubstanceType::Balanced;
                         random_substance.value = 0.0;
                     }
 
-                    //TODO: refactor this
-                    let substance_sprite = match random_substance.target_attribute {
-                        TargetAttribute::Attack => sub_attack_img.clone(),
-                        TargetAttribute::Health => sub_health_img.clone(),
-                        TargetAttribute::Immune => sub_immune_img.clone(),
-                        TargetAttribute::Speed => sub_speed_img.clone(),
-                    };
-                    let mut animation = AnimationClip::default();
-                    let mut player = AnimationPlayer::default();
-                    let anim_sub = Name::new(format!("anim_sub_{}", collected_count.0));
-                    collected_count.0 += 1;
-
-                    animation.add_curve_to_path(
-                        EntityPath {
-                            parts: vec![anim_sub.clone()],
-                        },
-                        VariableCurve {
-                            keyframe_timestamps: vec![0.0, 2.0],
-                            keyframes: Keyframes::Translation(vec![
-                                cell_trans.translation,
-                                collected_sub_display_trans.translation,
-                            ]),
-                        },
-                    );
-                    animation.add_curve_to_path(
-                        EntityPath {
-                            parts: vec![anim_sub.clone()],
-                        },
-                        VariableCurve {
-                            keyframe_timestamps: vec![0.0, 1.8, 2.0],
-                            keyframes: Keyframes::Scale(vec![
-                                cell_trans.scale,
-                                cell_trans.scale,
-                                Vec3::ZERO,
-                            ]),
-                        },
-                    );
+                    dropped_events.send(SubstanceDropped {
+                        name: random_substance.name.clone(),
+                        target_attribute: random_substance.target_attribute.clone(),
+                        value: random_substance.value,
+                        rarity: random_substance.rarity,
+                        position: cell_trans.translation,
+                    });
 
-                    player.play(animations.add(animation));
-                    commands.spawn((
-                        SpriteBundle {
-                            sprite: Sprite {
-                                custom_size: Some(Vec2::new(30.0, 30.0)),
+                    // the collected substance flies to the counter, skipped when running headless
+                    if let Ok((collected_sub_display_trans, mut text_2d, mut collected_count)) =
+                        collected_sub_display_query.get_single_mut()
+                    {
+                        let substance_sprite: Handle<Image> =
+                            asset_server.load(&random_substance.icon);
+                        let mut animation = AnimationClip::default();
+                        let mut player = AnimationPlayer::default();
+                        let anim_sub = Name::new(format!("anim_sub_{}", collected_count.0));
+                        collected_count.0 += 1;
+
+                        animation.add_curve_to_path(
+                            EntityPath {
+                                parts: vec![anim_sub.clone()],
+                            },
+                            VariableCurve {
+                                keyframe_timestamps: vec![0.0, 2.0],
+                                keyframes: Keyframes::Translation(vec![
+                                    cell_trans.translation,
+                                    collected_sub_display_trans.translation,
+                                ]),
+                            },
+                        );
+                        animation.add_curve_to_path(
+                            EntityPath {
+                                parts: vec![anim_sub.clone()],
+                            },
+                            VariableCurve {
+                                keyframe_timestamps: vec![0.0, 1.8, 2.0],
+                                keyframes: Keyframes::Scale(vec![
+                                    cell_trans.scale,
+                                    cell_trans.scale,
+                                    Vec3::ZERO,
+                                ]),
+                            },
+                        );
+
+                        player.play(animations.add(animation));
+                        commands.spawn((
+                            SpriteBundle {
+                                sprite: Sprite {
+                                    custom_size: Some(Vec2::new(30.0, 30.0)),
+                                    ..default()
+                                },
+                                texture: substance_sprite,
+                                transform: *cell_trans,
                                 ..default()
                             },
-                            texture: substance_sprite,
-                            transform: cell_trans.clone(),
-                            ..default()
-                        },
-                        OnGameScreen,
-                        player,
-                        anim_sub,
-                    ));
-
-                    if let Some(collected_text_section) = text_2d.sections.first_mut() {
-                        collected_text_section.value =
-                            format!("Collected substances: {}", collected_count.0);
+                            OnGameScreen,
+                            player,
+                            anim_sub,
+                        ));
+
+                        if let Some(collected_text_section) = text_2d.sections.first_mut() {
+                            collected_text_section.value =
+                                format!("Collected substances: {}", collected_count.0);
+                        }
                     }
                 }
             } else if let Some(maybe_goodcell) = maybe_goodcell {
                 player_resources.cell_army.remove(&maybe_goodcell.cell_id);
             }
 
+            killed_events.send(CellKilled {
+                entity: ent,
+                position: cell_trans.translation,
+                good_cell_id: maybe_goodcell.map(|good_cell| good_cell.cell_id),
+                archetype: maybe_archetype.copied(),
+            });
             commands.entity(ent).despawn_recursive();
         }
     }
 }
 
+// Decides the fate of the cells whose infection outgrew their immune system
 pub fn track_cell_infection(
-    mut commands: Commands,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    mut query: Query<(
-        Entity,
-        &GoodCell,
-        &mut CellAttribute,
-        &Handle<ColorMaterial>,
-    )>,
+    infection_model: Res<InfectionModel>,
+    mut game_rng: ResMut<GameRng>,
+    mut conversion_events: EventWriter<CellConversionEvent>,
+    mut recovered_events: EventWriter<CellRecovered>,
+    mut query: Query<(Entity, &GoodCell, &mut CellAttribute)>,
 ) {
-    for (ent, _, mut cell_attr, color_mat_handle) in query.iter_mut() {
-        if cell_attr.infection <= cell_attr.immune {
+    for (ent, _, mut cell_attr) in query.iter_mut() {
+        if !infection_model.is_overwhelmed(cell_attr.infection, cell_attr.immune) {
             continue;
         }
 
-        let golden_chance = rand::thread_rng().gen_range(1..=100);
+        let golden_chance = game_rng.combat.gen_range(1..=100);
 
         // there is small chance the cell will get stronger after infection
         // ref from Darkest Dungeon stress system!
-        if golden_chance <= 30 {
+        if golden_chance <= infection_model.golden_recovery_chance {
             cell_attr.health += 100.0;
             cell_attr.infection = 0.0;
             cell_attr.immune = 90.0;
+            recovered_events.send(CellRecovered { entity: ent });
         } else {
-            let color_mat = materials.get_mut(color_mat_handle).unwrap();
-            color_mat.color = Color::RED;
-            cell_attr.health = f32::max(25.0, cell_attr.health);
-            cell_attr.cell_attack.damage = f32::min(5.0, cell_attr.cell_attack.damage);
-            cell_attr.cell_attack.attack_rate = f32::max(8.0, cell_attr.cell_attack.attack_rate);
-            commands.entity(ent).remove::<GoodCell>().insert(BadCell);
+            conversion_events.send(CellConversionEvent {
+                entity: ent,
+                conversion: CellConversion::Infected,
+            });
+        }
+    }
+}
+
+// Turns cells into the other side in place: their components, stats and mesh are swapped
+// while the entity and its position carry over
+#[allow(clippy::too_many_arguments)]
+pub fn convert_cells(
+    mut commands: Commands,
+    mut conversion_events: EventReader<CellConversionEvent>,
+    mut converted_events: EventWriter<CellConverted>,
+    mut game_rng: ResMut<GameRng>,
+    mut player_resources: ResMut<PlayerResource>,
+    wave_table: Res<WaveTableResource>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut animations: ResMut<Assets<AnimationClip>>,
+    query: Query<(
+        &Transform,
+        &CellAttribute,
+        Option<&GoodCell>,
+        Option<&BadCell>,
+    )>,
+) {
+    let mut converted = vec![];
+    for conversion_event in conversion_events.read() {
+        if converted.contains(&conversion_event.entity) {
+            continue;
+        }
+        let Ok((cell_trans, cell_attr, maybe_goodcell, maybe_badcell)) =
+            query.get(conversion_event.entity)
+        else {
+            continue;
+        };
+
+        match (conversion_event.conversion, maybe_goodcell, maybe_badcell) {
+            (CellConversion::Infected, Some(good_cell), None) => {
+                let wave = wave_table.0.wave(player_resources.wave_num);
+                let archetype = BadCellArchetype::Basic;
+                let mut infected_attr = cell_attr.clone();
+                infected_attr.health = f32::max(25.0, infected_attr.health);
+                infected_attr.cell_attack.damage = f32::min(5.0, infected_attr.cell_attack.damage);
+                infected_attr.cell_attack.attack_rate =
+                    f32::max(8.0, infected_attr.cell_attack.attack_rate);
+                infected_attr.cure = 0.0;
+
+                // the cell is lost for good, even if the wave is won
+                player_resources.cell_army.remove(&good_cell.cell_id);
+
+                commands
+                    .entity(conversion_event.entity)
+                    .remove::<(
+                        GoodCell,
+                        GoodCellClass,
+                        CellStance,
+                        FormationSlot,
+                        SlotPath,
+                        CellExperience,
+                        HealthCap,
+                    )>()
+                    .insert((
+                        BadCell,
+                        archetype,
+                        infected_attr,
+                        MoveSpeed::roll(archetype, &mut game_rng.spawn),
+                        StatusEffects::default(),
+                        SearchRange {
+                            range: wave.search_radius,
+                        },
+                        AttackRange {
+                            range: archetype.attack_range().unwrap_or(wave.attack_range),
+                        },
+                    ))
+                    .despawn_descendants()
+                    .with_children(|child_builder| {
+                        spawn_bad_cell_mesh(
+                            child_builder,
+                            &mut meshes,
+                            &mut materials,
+                            &mut animations,
+                            &mut game_rng.spawn,
+                            archetype,
+                        );
+                    });
+            }
+            (CellConversion::Cured, None, Some(_)) => {
+                let cell_id = player_resources.good_cell_id.0;
+                player_resources.good_cell_id.0 += 1;
+                let cured_attr = CellAttribute::new_recruit();
+
+                // placed like any other new cell the next time the army is arranged
+                player_resources.cell_army.insert(
+                    cell_id,
+                    CellBundle {
+                        cell_trans: Vec3::ZERO,
+                        cell_attribute: cured_attr.clone(),
+                        stance: CellStance::default(),
+                        experience: CellExperience::default(),
+                        class: GoodCellClass::Naive,
+                        status_effects: StatusEffects::default(),
+                    },
+                );
+
+                commands
+                    .entity(conversion_event.entity)
+                    .remove::<(
+                        BadCell,
+                        BadCellArchetype,
+                        MoveSpeed,
+                        SearchRange,
+                        AttackRange,
+                        SplitOnDeath,
+                        // a cured core no longer leads the wave
+                        Boss,
+                        Minion,
+                    )>()
+                    .insert((
+                        GoodCell {
+                            cell_id,
+                            cell_size: GoodCellClass::Naive.size(),
+                        },
+                        GoodCellClass::Naive,
+                        CellStance::default(),
+                        FormationSlot(cell_trans.translation),
+                        SlotPath::default(),
+                        CellExperience::default(),
+                        HealthCap(cured_attr.health),
+                        StatusEffects::default(),
+                        cured_attr,
+                    ))
+                    .despawn_descendants()
+                    .with_children(|child_builder| {
+                        spawn_good_cell_mesh(
+                            child_builder,
+                            &mut meshes,
+                            &mut materials,
+                            &mut animations,
+                            &mut game_rng.spawn,
+                            GoodCellClass::Naive,
+                        );
+                    });
+            }
+            // already on the requested side
+            _ => continue,
+        }
+
+        converted_events.send(CellConverted {
+            entity: conversion_event.entity,
+            conversion: conversion_event.conversion,
+        });
+        converted.push(conversion_event.entity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::{
+        npc::goodcell::GoodCellId,
+        plugins::{playerresource::SubstanceIdGen, wave::WaveTable, wavestats::RunStats},
+    };
+
+    // Attacks a cell gets ready over ten seconds when its cooldown is ticked every frame_time
+    fn attacks_in_ten_seconds(attack_rate: f32, frame_time: Duration) -> u32 {
+        let mut cell_attack = CellAttack::new(attack_rate, 1.0);
+        let frames = (Duration::from_secs(10).as_secs_f64() / frame_time.as_secs_f64()).round();
+        (0..frames as u32)
+            .map(|_| cell_attack.tick(frame_time))
+            .sum()
+    }
+
+    #[test]
+    fn attacks_do_not_depend_on_the_frame_rate() {
+        for frame_time in [
+            Duration::from_millis(5),
+            Duration::from_micros(16_667),
+            Duration::from_millis(50),
+            Duration::from_millis(250),
+        ] {
+            assert_eq!(attacks_in_ten_seconds(0.5, frame_time), 20);
         }
     }
+
+    #[test]
+    fn a_longer_cooldown_means_fewer_attacks() {
+        let frame_time = Duration::from_micros(16_667);
+        assert_eq!(attacks_in_ten_seconds(1.0, frame_time), 10);
+        assert_eq!(attacks_in_ten_seconds(2.5, frame_time), 4);
+    }
+
+    #[test]
+    fn a_changed_attack_rate_applies_straight_away() {
+        let mut cell_attack = CellAttack::new(1.0, 1.0);
+        cell_attack.attack_rate = 0.25;
+        assert_eq!(cell_attack.tick(Duration::from_secs(1)), 4);
+    }
+
+    fn conversion_world(cell_army: BTreeMap<i32, CellBundle>) -> World {
+        let mut world = World::new();
+        world.init_resource::<Events<CellConversionEvent>>();
+        world.init_resource::<Events<CellConverted>>();
+        world.init_resource::<GameRng>();
+        world.insert_resource(WaveTableResource(WaveTable::default()));
+        world.init_resource::<Assets<Mesh>>();
+        world.init_resource::<Assets<ColorMaterial>>();
+        world.init_resource::<Assets<AnimationClip>>();
+        world.insert_resource(PlayerResource {
+            cell_army,
+            substance_collection: BTreeMap::new(),
+            loaded_substances: BTreeMap::new(),
+            fusion_bench: BTreeMap::new(),
+            good_cell_id: GoodCellId(8),
+            wave_num: 2,
+            substance_id_gen: SubstanceIdGen(0),
+            seed: 0,
+            run_stats: RunStats::default(),
+        });
+        world
+    }
+
+    fn convert(world: &mut World, entity: Entity, conversion: CellConversion) {
+        world
+            .resource_mut::<Events<CellConversionEvent>>()
+            .send(CellConversionEvent { entity, conversion });
+    }
+
+    #[test]
+    fn converted_cells_fully_change_sides() {
+        let good_cell_bundle = CellBundle {
+            cell_trans: Vec3::new(-20.0, 0.0, 0.0),
+            cell_attribute: CellAttribute::new_recruit(),
+            stance: CellStance::default(),
+            experience: CellExperience::default(),
+            class: GoodCellClass::TCell,
+            status_effects: StatusEffects::default(),
+        };
+        let mut world = conversion_world(BTreeMap::from([(3, good_cell_bundle.clone())]));
+        let infected = world
+            .spawn((
+                Transform::from_translation(good_cell_bundle.cell_trans),
+                good_cell_bundle.cell_attribute.clone(),
+                GoodCell {
+                    cell_id: 3,
+                    cell_size: GoodCellClass::TCell.size(),
+                },
+                GoodCellClass::TCell,
+                CellStance::default(),
+                FormationSlot(good_cell_bundle.cell_trans),
+                CellExperience::default(),
+                HealthCap(50.0),
+            ))
+            .id();
+        let cured = world
+            .spawn((
+                Transform::from_xyz(40.0, 10.0, 0.0),
+                CellAttribute {
+                    health: 2.0,
+                    ..CellAttribute::new_recruit()
+                },
+                BadCell,
+                BadCellArchetype::Basic,
+                MoveSpeed(1.0),
+                SearchRange { range: 1000.0 },
+                AttackRange { range: 10.0 },
+                Minion,
+            ))
+            .id();
+        convert(&mut world, infected, CellConversion::Infected);
+        convert(&mut world, cured, CellConversion::Cured);
+        // a second conversion in the same frame is ignored
+        convert(&mut world, cured, CellConversion::Infected);
+
+        world.run_system_once(convert_cells);
+
+        let infected_cell = world.entity(infected);
+        assert!(infected_cell.contains::<BadCell>());
+        assert!(infected_cell.contains::<MoveSpeed>());
+        assert!(infected_cell.contains::<SearchRange>());
+        assert!(infected_cell.contains::<AttackRange>());
+        assert!(!infected_cell.contains::<GoodCell>());
+        assert!(!infected_cell.contains::<FormationSlot>());
+        assert!(!infected_cell.contains::<SlotPath>());
+        assert!(!infected_cell.contains::<CellExperience>());
+        assert_eq!(
+            infected_cell.get::<Transform>().unwrap().translation,
+            good_cell_bundle.cell_trans
+        );
+
+        let cured_cell = world.entity(cured);
+        assert!(cured_cell.contains::<GoodCell>());
+        assert!(cured_cell.contains::<FormationSlot>());
+        assert!(cured_cell.contains::<CellStance>());
+        assert!(cured_cell.contains::<SlotPath>());
+        assert!(cured_cell.contains::<CellExperience>());
+        assert!(!cured_cell.contains::<BadCell>());
+        assert!(!cured_cell.contains::<MoveSpeed>());
+        assert!(!cured_cell.contains::<Minion>());
+        assert!(!cured_cell.contains::<SearchRange>());
+        assert_eq!(cured_cell.get::<GoodCell>().unwrap().cell_id, 8);
+
+        let player_resources = world.resource::<PlayerResource>();
+        assert!(!player_resources.cell_army.contains_key(&3));
+        let recruit = &player_resources.cell_army[&8];
+        assert_eq!(recruit.cell_trans, Vec3::ZERO);
+        assert_eq!(recruit.class, GoodCellClass::Naive);
+        assert_eq!(player_resources.good_cell_id.0, 9);
+
+        let converted: Vec<(Entity, CellConversion)> = world
+            .resource_mut::<Events<CellConverted>>()
+            .drain()
+            .map(|converted| (converted.entity, converted.conversion))
+            .collect();
+        assert_eq!(
+            converted,
+            vec![
+                (infected, CellConversion::Infected),
+                (cured, CellConversion::Cured)
+            ]
+        );
+    }
 }
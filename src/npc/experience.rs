@@ -0,0 +1,299 @@
+// Good cells earn experience in combat and level up between waves,
+// every level grows their stats and lets the player pick a perk
+use std::fmt;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    cell::CellAttribute,
+    goodcell::GoodCell,
+    statuseffect::{StatusEffect, StatusEffectKind},
+};
+
+const XP_PER_DAMAGE: f32 = 1.0;
+const XP_PER_KILL: f32 = 25.0;
+// a level costs this much experience times the current level
+const XP_PER_LEVEL: f32 = 100.0;
+const LEVEL_HEALTH_GROWTH: f32 = 10.0;
+const LEVEL_DAMAGE_GROWTH: f32 = 2.0;
+
+// Each rank of a perk adds this much
+pub const SPLASH_RADIUS: f32 = 30.0;
+const SPLASH_DAMAGE_PER_RANK: f32 = 0.3;
+const REGENERATION_PER_RANK: f32 = 2.0; // health per second
+const INFECTION_RESISTANCE_PER_RANK: f32 = 0.25;
+const MAX_INFECTION_RESISTANCE: f32 = 0.75;
+const VENOM_POISON_PER_RANK: f32 = 1.0; // health per second
+const VENOM_DURATION: f32 = 3.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum CellPerk {
+    // hits also damage the bad cells around the target
+    Splash,
+    // heals back the damage taken during a wave
+    Regeneration,
+    // less infection sticks to the cell
+    InfectionResistance,
+    // hits poison the target
+    Venom,
+}
+
+impl CellPerk {
+    pub const ALL: [CellPerk; 4] = [
+        CellPerk::Splash,
+        CellPerk::Regeneration,
+        CellPerk::InfectionResistance,
+        CellPerk::Venom,
+    ];
+}
+
+impl fmt::Display for CellPerk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CellPerk::Splash => write!(f, "Splash"),
+            CellPerk::Regeneration => write!(f, "Regeneration"),
+            CellPerk::InfectionResistance => write!(f, "Infection resistance"),
+            CellPerk::Venom => write!(f, "Venom"),
+        }
+    }
+}
+
+#[derive(Component, Clone, Debug, Serialize, Deserialize)]
+pub struct CellExperience {
+    pub level: u32,
+    pub xp: f32,
+    // a perk picked several times stacks
+    pub perks: Vec<CellPerk>,
+    // level ups that still wait for a perk to be picked
+    pub perk_choices: u32,
+}
+
+impl Default for CellExperience {
+    fn default() -> Self {
+        Self {
+            level: 1,
+            xp: 0.0,
+            perks: vec![],
+            perk_choices: 0,
+        }
+    }
+}
+
+impl CellExperience {
+    pub fn xp_to_next_level(&self) -> f32 {
+        XP_PER_LEVEL * self.level as f32
+    }
+
+    // Experience for a hit that took health_before down by damage
+    pub fn record_hit(&mut self, health_before: f32, damage: f32) {
+        if health_before <= 0.0 {
+            return;
+        }
+        self.xp += f32::min(damage, health_before) * XP_PER_DAMAGE;
+        if damage >= health_before {
+            self.xp += XP_PER_KILL;
+        }
+    }
+
+    // Spend the experience on as many levels as it pays for, called once the wave is cleared
+    pub fn level_up(&mut self, cell_attr: &mut CellAttribute) {
+        while self.xp >= self.xp_to_next_level() {
+            self.xp -= self.xp_to_next_level();
+            self.level += 1;
+            self.perk_choices += 1;
+            cell_attr.health += LEVEL_HEALTH_GROWTH;
+            cell_attr.cell_attack.damage += LEVEL_DAMAGE_GROWTH;
+        }
+    }
+
+    pub fn choose_perk(&mut self, perk: CellPerk) -> bool {
+        if self.perk_choices == 0 {
+            return false;
+        }
+        self.perk_choices -= 1;
+        self.perks.push(perk);
+        true
+    }
+
+    pub fn perk_rank(&self, perk: CellPerk) -> usize {
+        self.perks.iter().filter(|owned| **owned == perk).count()
+    }
+
+    pub fn splash_damage(&self, damage: f32) -> f32 {
+        damage * SPLASH_DAMAGE_PER_RANK * self.perk_rank(CellPerk::Splash) as f32
+    }
+
+    pub fn regeneration(&self) -> f32 {
+        REGENERATION_PER_RANK * self.perk_rank(CellPerk::Regeneration) as f32
+    }
+
+    // How much of an infection sticks to the cell
+    pub fn infection_factor(&self) -> f32 {
+        let resistance =
+            INFECTION_RESISTANCE_PER_RANK * self.perk_rank(CellPerk::InfectionResistance) as f32;
+        1.0 - f32::min(resistance, MAX_INFECTION_RESISTANCE)
+    }
+
+    // What a hit of the cell leaves on the bad cell
+    pub fn on_hit_effect(&self) -> Option<StatusEffect> {
+        let rank = self.perk_rank(CellPerk::Venom);
+        if rank == 0 {
+            return None;
+        }
+        Some(StatusEffect::new(
+            StatusEffectKind::Poison,
+            VENOM_POISON_PER_RANK * rank as f32,
+            VENOM_DURATION,
+        ))
+    }
+}
+
+// The health a good cell started the wave with, regeneration does not heal past it
+#[derive(Component)]
+pub struct HealthCap(pub f32);
+
+pub fn regenerate(
+    time: Res<Time>,
+    mut goodcell_query: Query<(&CellExperience, &HealthCap, &mut CellAttribute), With<GoodCell>>,
+) {
+    for (experience, health_cap, mut cell_attr) in goodcell_query.iter_mut() {
+        let regeneration = experience.regeneration();
+        // dead cells stay dead
+        if regeneration <= 0.0 || cell_attr.health <= 0.0 || cell_attr.health >= health_cap.0 {
+            continue;
+        }
+
+        cell_attr.health = f32::min(
+            cell_attr.health + regeneration * time.delta_seconds(),
+            health_cap.0,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::npc::cell::CellAttack;
+
+    fn cell_attribute(health: f32) -> CellAttribute {
+        CellAttribute {
+            health,
+            immune: 10.0,
+            infection: 0.0,
+            cure: 0.0,
+            infection_proof: false,
+            cell_attack: CellAttack::new(1.0, 5.0),
+        }
+    }
+
+    #[test]
+    fn hits_earn_experience_for_the_damage_they_did() {
+        let mut experience = CellExperience::default();
+        experience.record_hit(50.0, 10.0);
+        assert_eq!(experience.xp, 10.0 * XP_PER_DAMAGE);
+
+        // overkill only counts the health that was left, plus the kill
+        experience.record_hit(4.0, 10.0);
+        assert_eq!(experience.xp, 14.0 * XP_PER_DAMAGE + XP_PER_KILL);
+
+        // a cell that was already dead earns nothing
+        experience.record_hit(0.0, 10.0);
+        assert_eq!(experience.xp, 14.0 * XP_PER_DAMAGE + XP_PER_KILL);
+    }
+
+    #[test]
+    fn levels_cost_more_every_level() {
+        let mut experience = CellExperience {
+            xp: XP_PER_LEVEL - 1.0,
+            ..default()
+        };
+        let mut cell_attr = cell_attribute(50.0);
+        experience.level_up(&mut cell_attr);
+        assert_eq!(experience.level, 1);
+        assert_eq!(cell_attr.health, 50.0);
+
+        // enough for level 2 and 3, with some left over
+        experience.xp = XP_PER_LEVEL * 3.0 + 5.0;
+        experience.level_up(&mut cell_attr);
+        assert_eq!(experience.level, 3);
+        assert_eq!(experience.xp, 5.0);
+        assert_eq!(experience.perk_choices, 2);
+        assert_eq!(cell_attr.health, 50.0 + 2.0 * LEVEL_HEALTH_GROWTH);
+        assert_eq!(
+            cell_attr.cell_attack.damage,
+            5.0 + 2.0 * LEVEL_DAMAGE_GROWTH
+        );
+    }
+
+    #[test]
+    fn a_perk_needs_a_pending_level() {
+        let mut experience = CellExperience::default();
+        assert!(!experience.choose_perk(CellPerk::Splash));
+        assert!(experience.perks.is_empty());
+
+        experience.perk_choices = 1;
+        assert!(experience.choose_perk(CellPerk::Splash));
+        assert_eq!(experience.perk_choices, 0);
+        // picking it again needs another level
+        assert!(!experience.choose_perk(CellPerk::Splash));
+        assert_eq!(experience.perk_rank(CellPerk::Splash), 1);
+    }
+
+    #[test]
+    fn a_perk_picked_again_ranks_up() {
+        let mut experience = CellExperience {
+            perk_choices: 4,
+            ..default()
+        };
+        for _ in 0..4 {
+            assert!(experience.choose_perk(CellPerk::InfectionResistance));
+        }
+        assert_eq!(experience.perk_rank(CellPerk::InfectionResistance), 4);
+        assert_eq!(experience.perk_rank(CellPerk::Venom), 0);
+        // the resistance stops at its maximum
+        assert_eq!(
+            experience.infection_factor(),
+            1.0 - MAX_INFECTION_RESISTANCE
+        );
+        assert_eq!(experience.on_hit_effect(), None);
+    }
+
+    #[test]
+    fn regeneration_stops_at_the_health_cap() {
+        let mut world = World::new();
+        let mut time = Time::<()>::default();
+        time.advance_by(Duration::from_secs(1));
+        world.insert_resource(time);
+        let mut spawn_cell = |health: f32| {
+            world
+                .spawn((
+                    GoodCell {
+                        cell_id: 0,
+                        cell_size: 5.0,
+                    },
+                    CellExperience {
+                        perks: vec![CellPerk::Regeneration],
+                        ..default()
+                    },
+                    HealthCap(50.0),
+                    cell_attribute(health),
+                ))
+                .id()
+        };
+        let wounded = spawn_cell(40.0);
+        let almost_healed = spawn_cell(49.0);
+        let dead = spawn_cell(0.0);
+
+        world.run_system_once(regenerate);
+
+        let health = |entity: Entity| world.get::<CellAttribute>(entity).unwrap().health;
+        assert_eq!(health(wounded), 40.0 + REGENERATION_PER_RANK);
+        assert_eq!(health(almost_healed), 50.0);
+        assert_eq!(health(dead), 0.0);
+    }
+}
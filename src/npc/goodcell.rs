@@ -1,18 +1,37 @@
-use std::time::Duration;
+use std::fmt;
 
 use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
-use rand::Rng;
+use rand::{rngs::StdRng, Rng};
+use serde::{Deserialize, Serialize};
 
-use crate::plugins::{game::OnGameScreen, playerresource::PlayerResource};
+use crate::core::{
+    arena::Arena,
+    gamerng::GameRng,
+    navigation::{self, FlowField, FLOW_FIELD_TILE_SIZE},
+    spatialgrid::CellGrid,
+};
+use crate::plugins::{combatlog::CellDamaged, game::OnGameScreen, playerresource::PlayerResource};
 
 use super::{
     badcell::BadCell,
-    cell::{Cell, CellAttribute, Collider},
+    cell::{Cell, CellAttack, CellAttribute, CellConversion, CellConversionEvent, Collider},
+    experience::{CellExperience, HealthCap, SPLASH_RADIUS},
+    statuseffect::StatusEffects,
 };
 
 pub const GOOD_CELL_ATTACK_RANGE: f32 = 10.0;
 pub const GOOD_CELL_SPAWN_RADIUS: f32 = 200.0;
 pub const GOOD_CELL_SIZE: f32 = 15.0;
+pub const GOOD_CELL_MOVE_SPEED: f32 = 60.0;
+// how far from its formation slot a cell goes looking for a fight
+pub const GOOD_CELL_ENGAGE_RADIUS: f32 = 150.0;
+// guards defend everything within this distance of the centre
+pub const GOOD_CELL_GUARD_RADIUS: f32 = 120.0;
+// macrophages swallow bad cells this weak whole and heal from it
+const MACROPHAGE_ENGULF_HEALTH: f32 = 20.0;
+const MACROPHAGE_ENGULF_HEAL: f32 = 10.0;
+// neutrophils burn out over the course of a wave
+const NEUTROPHIL_DECAY_PER_SECOND: f32 = 1.5;
 
 #[derive(Component)]
 pub struct GoodCell {
@@ -20,115 +39,613 @@ pub struct GoodCell {
     pub cell_size: f32,
 }
 
-#[derive(Resource)]
+#[derive(Resource, Serialize, Deserialize)]
 pub struct GoodCellId(pub i32);
 
+#[derive(Component, Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum CellStance {
+    // stay on the formation slot and only hit what comes close
+    #[default]
+    HoldPosition,
+    InterceptNearest,
+    GuardCentre,
+    ChaseWeakest,
+}
+
+impl CellStance {
+    pub fn next(&self) -> Self {
+        match self {
+            CellStance::HoldPosition => CellStance::InterceptNearest,
+            CellStance::InterceptNearest => CellStance::GuardCentre,
+            CellStance::GuardCentre => CellStance::ChaseWeakest,
+            CellStance::ChaseWeakest => CellStance::HoldPosition,
+        }
+    }
+}
+
+impl fmt::Display for CellStance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CellStance::HoldPosition => write!(f, "Hold"),
+            CellStance::InterceptNearest => write!(f, "Intercept"),
+            CellStance::GuardCentre => write!(f, "Guard"),
+            CellStance::ChaseWeakest => write!(f, "Chase weakest"),
+        }
+    }
+}
+
+// The kind of immune cell, decided by the dominant substance of the brew that created it
+#[derive(Component, Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum GoodCellClass {
+    // brewed without a dominant substance, or cured from a bad cell
+    #[default]
+    Naive,
+    // tanky, engulfs weakened bad cells
+    Macrophage,
+    // hits hard up close
+    TCell,
+    // shoots antibodies from range
+    BCell,
+    // fast, but wears out during a wave
+    Neutrophil,
+}
+
+impl GoodCellClass {
+    // Stats of a freshly brewed cell of this class
+    pub fn recruit_attribute(&self) -> CellAttribute {
+        let mut cell_attr = CellAttribute::new_recruit();
+        match self {
+            GoodCellClass::Naive => {}
+            GoodCellClass::Macrophage => {
+                cell_attr.health = 120.0;
+                cell_attr.immune = 40.0;
+                cell_attr.cell_attack = CellAttack::new(0.8, 15.0);
+            }
+            GoodCellClass::TCell => {
+                cell_attr.health = 40.0;
+                cell_attr.immune = 25.0;
+                cell_attr.cell_attack = CellAttack::new(0.4, 35.0);
+            }
+            GoodCellClass::BCell => {
+                cell_attr.health = 35.0;
+                cell_attr.immune = 35.0;
+                cell_attr.cell_attack = CellAttack::new(1.0, 12.0);
+            }
+            GoodCellClass::Neutrophil => {
+                cell_attr.health = 30.0;
+                cell_attr.immune = 20.0;
+                cell_attr.cell_attack = CellAttack::new(0.3, 15.0);
+            }
+        }
+        cell_attr
+    }
+
+    pub fn attack_range(&self) -> f32 {
+        match self {
+            GoodCellClass::BCell => 80.0,
+            _ => GOOD_CELL_ATTACK_RANGE,
+        }
+    }
+
+    pub fn speed_multiplier(&self) -> f32 {
+        match self {
+            GoodCellClass::Macrophage => 0.7,
+            GoodCellClass::Neutrophil => 2.0,
+            _ => 1.0,
+        }
+    }
+
+    pub fn size(&self) -> f32 {
+        match self {
+            GoodCellClass::Macrophage => 20.0,
+            GoodCellClass::Neutrophil => 12.0,
+            _ => GOOD_CELL_SIZE,
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            GoodCellClass::Naive => Color::GREEN,
+            GoodCellClass::Macrophage => Color::SEA_GREEN,
+            GoodCellClass::TCell => Color::LIME_GREEN,
+            GoodCellClass::BCell => Color::TEAL,
+            GoodCellClass::Neutrophil => Color::YELLOW_GREEN,
+        }
+    }
+
+    pub fn mesh(&self) -> Mesh {
+        let size = self.size();
+        match self {
+            GoodCellClass::TCell => shape::RegularPolygon::new(size, 4).into(),
+            GoodCellClass::BCell => shape::RegularPolygon::new(size, 6).into(),
+            GoodCellClass::Neutrophil => shape::RegularPolygon::new(size, 3).into(),
+            _ => shape::Circle::new(size).into(),
+        }
+    }
+}
+
+impl fmt::Display for GoodCellClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GoodCellClass::Naive => write!(f, "Naive cell"),
+            GoodCellClass::Macrophage => write!(f, "Macrophage"),
+            GoodCellClass::TCell => write!(f, "T-cell"),
+            GoodCellClass::BCell => write!(f, "B-cell"),
+            GoodCellClass::Neutrophil => write!(f, "Neutrophil"),
+        }
+    }
+}
+
+// Where the player put the cell in Cell Arrangement
+#[derive(Component)]
+pub struct FormationSlot(pub Vec3);
+
+// The way around the walls back to the formation slot. It is searched once when the cell
+// heads back and then followed, instead of searched again every tick
+#[derive(Component, Default)]
+pub struct SlotPath {
+    // the next waypoint is last, None until searched
+    waypoints: Option<Vec<Vec2>>,
+}
+
+impl SlotPath {
+    pub fn clear(&mut self) {
+        self.waypoints = None;
+    }
+
+    // The waypoint to head for from position, searched again when a wall is in the way
+    // because the cell was pushed off the path
+    fn next_waypoint(
+        &mut self,
+        position: Vec2,
+        slot: Vec2,
+        flow_field: &FlowField,
+        arena: &Arena,
+    ) -> Option<Vec2> {
+        let waypoint = self.follow(position, slot, flow_field)?;
+        if arena.is_line_clear(position, waypoint, 0.0) {
+            return Some(waypoint);
+        }
+        self.clear();
+        self.follow(position, slot, flow_field)
+    }
+
+    fn follow(&mut self, position: Vec2, slot: Vec2, flow_field: &FlowField) -> Option<Vec2> {
+        let waypoints = self.waypoints.get_or_insert_with(|| {
+            let mut path = flow_field.find_path(position, slot).unwrap_or_default();
+            path.reverse();
+            path
+        });
+        while waypoints
+            .last()
+            .is_some_and(|waypoint| waypoint.distance(position) < FLOW_FIELD_TILE_SIZE / 2.0)
+        {
+            waypoints.pop();
+        }
+        waypoints.last().copied()
+    }
+}
+
 pub fn spawn_good_cells(
     mut commands: Commands,
+    mut game_rng: ResMut<GameRng>,
+    arena: Res<Arena>,
     mut player_resources: ResMut<PlayerResource>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     mut animations: ResMut<Assets<AnimationClip>>,
 ) {
-    let mut cell_count = 0;
-
     for (id, good_cell_bundle) in player_resources.cell_army.iter_mut() {
         let good_cell_attr = &good_cell_bundle.cell_attribute;
-        let mut animation = AnimationClip::default();
-        let mut player = AnimationPlayer::default();
         let mut origin_point = good_cell_bundle.cell_trans;
 
         if origin_point == Vec3::ZERO {
-            origin_point.x =
-                rand::thread_rng().gen_range(-GOOD_CELL_SPAWN_RADIUS..=GOOD_CELL_SPAWN_RADIUS);
-            origin_point.y =
-                rand::thread_rng().gen_range(-GOOD_CELL_SPAWN_RADIUS..=GOOD_CELL_SPAWN_RADIUS);
+            origin_point.x = game_rng
+                .spawn
+                .gen_range(-GOOD_CELL_SPAWN_RADIUS..=GOOD_CELL_SPAWN_RADIUS);
+            origin_point.y = game_rng
+                .spawn
+                .gen_range(-GOOD_CELL_SPAWN_RADIUS..=GOOD_CELL_SPAWN_RADIUS);
         }
+        // the arena of this wave may have a wall where the cell was placed
+        let origin_point = arena
+            .push_out(origin_point.truncate(), good_cell_bundle.class.size())
+            .extend(origin_point.z);
 
-        // TODO: refactor the below code
-        let anim_cell = Name::new(format!("anim_cell_{cell_count}"));
+        commands
+            .spawn((
+                SpatialBundle::from_transform(Transform::from_translation(origin_point)),
+                Cell,
+                GoodCell {
+                    cell_id: *id,
+                    cell_size: good_cell_bundle.class.size(),
+                },
+                good_cell_bundle.class,
+                good_cell_bundle.stance,
+                FormationSlot(origin_point),
+                SlotPath::default(),
+                good_cell_bundle.experience.clone(),
+                HealthCap(good_cell_attr.health),
+                good_cell_bundle.status_effects.clone(),
+                good_cell_attr.clone(),
+                Collider,
+                OnGameScreen, // TODO: find a better way to add this component to a cell
+            ))
+            .with_children(|child_builder| {
+                spawn_good_cell_mesh(
+                    child_builder,
+                    &mut meshes,
+                    &mut materials,
+                    &mut animations,
+                    &mut game_rng.spawn,
+                    good_cell_bundle.class,
+                );
+            });
+    }
+}
 
-        let rand_keyframe_1 = Vec3::new(
-            rand::thread_rng().gen_range(-5.0..=5.0),
-            rand::thread_rng().gen_range(-5.0..=5.0),
-            0.0,
-        );
-        let rand_keyframe_2 = Vec3::new(
-            rand::thread_rng().gen_range(-5.0..=5.0),
-            rand::thread_rng().gen_range(-5.0..=5.0),
-            0.0,
-        );
-        let rand_keyframe_3 = Vec3::new(
-            rand::thread_rng().gen_range(-5.0..=5.0),
-            rand::thread_rng().gen_range(-5.0..=5.0),
-            0.0,
-        );
+// The wobbling mesh of a good cell, spawned as a child so the cell itself is free to move
+pub fn spawn_good_cell_mesh(
+    child_builder: &mut ChildBuilder,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    animations: &mut Assets<AnimationClip>,
+    rng: &mut StdRng,
+    class: GoodCellClass,
+) {
+    let mut animation = AnimationClip::default();
+    let mut player = AnimationPlayer::default();
 
-        animation.add_curve_to_path(
-            EntityPath {
-                parts: vec![anim_cell.clone()],
-            },
-            VariableCurve {
-                keyframe_timestamps: vec![0.0, 1.0, 2.0, 3.0, 4.0],
-                keyframes: Keyframes::Translation(vec![
-                    origin_point,
-                    origin_point + rand_keyframe_1,
-                    origin_point + rand_keyframe_2,
-                    origin_point + rand_keyframe_3,
-                    // in case seamless looping is wanted, the last keyframe should
-                    // be the same as the first one
-                    origin_point,
-                ]),
-            },
-        );
+    // TODO: refactor the below code
+    let anim_cell = Name::new("anim_cell");
+    let child_origin = Vec3::new(0., 0., 0.);
 
-        player.play(animations.add(animation)).repeat();
-
-        commands.spawn((
-            MaterialMesh2dBundle {
-                mesh: meshes.add(shape::Circle::new(GOOD_CELL_SIZE).into()).into(),
-                material: materials.add(ColorMaterial::from(Color::GREEN)),
-                transform: Transform::from_translation(origin_point),
-                ..default()
-            },
-            anim_cell,
-            player,
-            Cell,
-            GoodCell {
-                cell_id: id.clone(),
-                cell_size: 15.0,
-            },
-            good_cell_attr.clone(),
-            Collider,
-            OnGameScreen, // TODO: find a better way to add this component to a cell
-        ));
-
-        cell_count += 1;
+    let rand_keyframe_1 = Vec3::new(rng.gen_range(-5.0..=5.0), rng.gen_range(-5.0..=5.0), 0.0);
+    let rand_keyframe_2 = Vec3::new(rng.gen_range(-5.0..=5.0), rng.gen_range(-5.0..=5.0), 0.0);
+    let rand_keyframe_3 = Vec3::new(rng.gen_range(-5.0..=5.0), rng.gen_range(-5.0..=5.0), 0.0);
+
+    animation.add_curve_to_path(
+        EntityPath {
+            parts: vec![anim_cell.clone()],
+        },
+        VariableCurve {
+            keyframe_timestamps: vec![0.0, 1.0, 2.0, 3.0, 4.0],
+            keyframes: Keyframes::Translation(vec![
+                child_origin,
+                child_origin + rand_keyframe_1,
+                child_origin + rand_keyframe_2,
+                child_origin + rand_keyframe_3,
+                // in case seamless looping is wanted, the last keyframe should
+                // be the same as the first one
+                child_origin,
+            ]),
+        },
+    );
+
+    player.play(animations.add(animation)).repeat();
+
+    child_builder.spawn((
+        MaterialMesh2dBundle {
+            mesh: meshes.add(class.mesh()).into(),
+            material: materials.add(ColorMaterial::from(class.color())),
+            transform: Transform::from_translation(child_origin),
+            ..default()
+        },
+        anim_cell,
+        player,
+    ));
+}
+
+// Picks a destination for every good cell according to its stance and moves it there.
+// Cells only engage enemies near their formation slot (or the centre when guarding)
+// and return to the slot once nothing is left to fight
+pub fn steer(
+    time: Res<Time>,
+    cell_grid: Res<CellGrid>,
+    flow_field: Res<FlowField>,
+    arena: Res<Arena>,
+    mut goodcell_query: Query<
+        (
+            &mut Transform,
+            &CellStance,
+            &GoodCellClass,
+            &FormationSlot,
+            &mut SlotPath,
+        ),
+        With<GoodCell>,
+    >,
+    badcell_query: Query<&CellAttribute, With<BadCell>>,
+) {
+    for (mut good_cell_trans, stance, class, formation_slot, mut slot_path) in
+        goodcell_query.iter_mut()
+    {
+        let maybe_target = match stance {
+            CellStance::HoldPosition => None,
+            CellStance::InterceptNearest => cell_grid
+                .bad_cells
+                .nearest_in_radius(formation_slot.0, GOOD_CELL_ENGAGE_RADIUS),
+            CellStance::GuardCentre => cell_grid
+                .bad_cells
+                .nearest_in_radius(Vec3::ZERO, GOOD_CELL_GUARD_RADIUS),
+            CellStance::ChaseWeakest => cell_grid
+                .bad_cells
+                .query_radius(formation_slot.0, GOOD_CELL_ENGAGE_RADIUS)
+                .filter_map(|(bad_cell_ent, bad_cell_pos)| {
+                    badcell_query
+                        .get(bad_cell_ent)
+                        .ok()
+                        .map(|badcell_attr| (bad_cell_ent, bad_cell_pos, badcell_attr.health))
+                })
+                .min_by(|(_, _, a), (_, _, b)| a.total_cmp(b))
+                .map(|(bad_cell_ent, bad_cell_pos, _)| (bad_cell_ent, bad_cell_pos)),
+        };
+
+        // stop just inside attack range of a target, or right on the slot
+        let (destination, stop_distance) = match maybe_target {
+            Some((_, bad_cell_pos)) => (bad_cell_pos, class.attack_range() * 0.5),
+            None => (formation_slot.0, 0.0),
+        };
+
+        let position = good_cell_trans.translation;
+        let offset = destination - position;
+        let distance = offset.length();
+        if distance <= stop_distance {
+            continue;
+        }
+
+        let move_speed = GOOD_CELL_MOVE_SPEED * class.speed_multiplier();
+        let step = move_speed * time.delta_seconds();
+        // the last step lands right on the spot
+        if distance - stop_distance <= step {
+            good_cell_trans.translation += offset / distance * (distance - stop_distance);
+            continue;
+        }
+
+        // targets are reached around the walls like the bad cells reach theirs,
+        // the way back to the slot is searched for when a wall is in between
+        let straight = offset.truncate() / distance;
+        let desired = match maybe_target {
+            Some(_) => {
+                slot_path.clear();
+                flow_field
+                    .towards_bad_cells(position.truncate())
+                    .unwrap_or(straight)
+            }
+            None if arena.is_line_clear(
+                position.truncate(),
+                destination.truncate(),
+                class.size(),
+            ) =>
+            {
+                slot_path.clear();
+                straight
+            }
+            None => slot_path
+                .next_waypoint(
+                    position.truncate(),
+                    destination.truncate(),
+                    &flow_field,
+                    &arena,
+                )
+                .and_then(|waypoint| (waypoint - position.truncate()).try_normalize())
+                .unwrap_or(straight),
+        };
+        let direction = navigation::steer(
+            position,
+            desired,
+            &cell_grid.good_cells,
+            &arena,
+            class.size(),
+        );
+        good_cell_trans.translation += (direction * step).extend(0.0);
     }
 }
 
+// Each cell hits every bad cell in range whenever its cooldown is up, B-cells
+// from further away. Cells with a cure cure weakened bad cells instead of hitting them,
+// macrophages engulf them. Damage dealt and kills earn the cell experience
+#[allow(clippy::type_complexity)]
 pub fn attack(
-    mut goodcell_query: Query<(&mut Transform, &mut CellAttribute), With<GoodCell>>,
+    time: Res<Time>,
+    cell_grid: Res<CellGrid>,
+    mut conversion_events: EventWriter<CellConversionEvent>,
+    mut damaged_events: EventWriter<CellDamaged>,
+    mut goodcell_query: Query<
+        (
+            Entity,
+            &Transform,
+            &GoodCellClass,
+            &mut CellAttribute,
+            &mut CellExperience,
+            &StatusEffects,
+        ),
+        With<GoodCell>,
+    >,
     mut collision_query: Query<
-        (&Transform, &mut CellAttribute),
+        (&mut CellAttribute, &mut StatusEffects),
         (With<Collider>, With<BadCell>, Without<GoodCell>),
     >,
 ) {
-    for (good_cell_trans, mut goodcell_attr) in goodcell_query.iter_mut() {
-        for (bad_cell_trans, mut badcell_attr) in collision_query.iter_mut() {
-            if Vec3::distance(good_cell_trans.translation, bad_cell_trans.translation)
-                <= GOOD_CELL_ATTACK_RANGE
+    for (
+        good_cell_ent,
+        good_cell_trans,
+        class,
+        mut goodcell_attr,
+        mut experience,
+        goodcell_status,
+    ) in goodcell_query.iter_mut()
+    {
+        // every bad cell in range is hit. The grid is built before the tick,
+        // so it still holds the cells other good cells have killed since
+        let targets: Vec<(Entity, Vec3)> = cell_grid
+            .bad_cells
+            .query_radius(good_cell_trans.translation, class.attack_range())
+            .filter(|(bad_cell_ent, _)| {
+                collision_query
+                    .get(*bad_cell_ent)
+                    .is_ok_and(|(badcell_attr, _)| badcell_attr.health > 0.0)
+            })
+            .collect();
+        if targets.is_empty() {
+            continue;
+        }
+
+        let attacks = goodcell_attr
+            .cell_attack
+            .tick(time.delta().div_f32(goodcell_status.slow_factor()));
+        let damage = goodcell_attr.cell_attack.damage;
+        for (bad_cell_ent, bad_cell_pos) in targets.iter().copied() {
+            let Ok((mut badcell_attr, mut badcell_status)) = collision_query.get_mut(bad_cell_ent)
+            else {
+                continue;
+            };
+
+            let mut hits = 0;
+            for _ in 0..attacks {
+                if badcell_attr.health <= 0.0 {
+                    break;
+                }
+                if badcell_attr.health <= goodcell_attr.cure {
+                    conversion_events.send(CellConversionEvent {
+                        entity: bad_cell_ent,
+                        conversion: CellConversion::Cured,
+                    });
+                    break;
+                }
+                let damage = if *class == GoodCellClass::Macrophage
+                    && badcell_attr.health <= MACROPHAGE_ENGULF_HEALTH
+                {
+                    goodcell_attr.health += MACROPHAGE_ENGULF_HEAL;
+                    badcell_attr.health
+                } else {
+                    damage
+                };
+                let damage = badcell_status.absorb(damage);
+                experience.record_hit(badcell_attr.health, damage);
+                badcell_attr.inflict_dmg(damage);
+                if let Some(effect) = experience.on_hit_effect() {
+                    badcell_status.apply(effect);
+                }
+                hits += 1;
+                damaged_events.send(CellDamaged {
+                    entity: bad_cell_ent,
+                    attacker: good_cell_ent,
+                    amount: damage,
+                    health: badcell_attr.health,
+                });
+            }
+
+            let splash_damage = experience.splash_damage(damage) * hits as f32;
+            if splash_damage <= 0.0 {
+                continue;
+            }
+            // cells in range already take the full hit
+            for (splashed_ent, _) in cell_grid
+                .bad_cells
+                .query_radius(bad_cell_pos, SPLASH_RADIUS)
             {
-                let attack_rate = goodcell_attr.cell_attack.attack_rate;
-                goodcell_attr
-                    .cell_attack
-                    .timer
-                    .tick(Duration::from_secs_f32(attack_rate));
-                if goodcell_attr.cell_attack.timer.finished() {
-                    let damage = goodcell_attr.cell_attack.damage;
-                    badcell_attr.inflict_dmg(damage);
+                if targets
+                    .iter()
+                    .any(|(target_ent, _)| *target_ent == splashed_ent)
+                {
+                    continue;
                 }
+                let Ok((mut splashed_attr, mut splashed_status)) =
+                    collision_query.get_mut(splashed_ent)
+                else {
+                    continue;
+                };
+                if splashed_attr.health <= 0.0 {
+                    continue;
+                }
+                let splash_damage = splashed_status.absorb(splash_damage);
+                experience.record_hit(splashed_attr.health, splash_damage);
+                splashed_attr.inflict_dmg(splash_damage);
+                damaged_events.send(CellDamaged {
+                    entity: splashed_ent,
+                    attacker: good_cell_ent,
+                    amount: splash_damage,
+                    health: splashed_attr.health,
+                });
             }
         }
     }
 }
+
+pub fn wear_out_neutrophils(
+    time: Res<Time>,
+    mut goodcell_query: Query<(&GoodCellClass, &mut CellAttribute), With<GoodCell>>,
+) {
+    for (class, mut cell_attr) in goodcell_query.iter_mut() {
+        if *class == GoodCellClass::Neutrophil {
+            cell_attr.inflict_dmg(NEUTROPHIL_DECAY_PER_SECOND * time.delta_seconds());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::arena::{ArenaShape, Obstacle};
+
+    fn walled_arena() -> Arena {
+        Arena {
+            shape: ArenaShape::TissueWalls,
+            obstacles: vec![Obstacle {
+                center: Vec2::ZERO,
+                half_extents: Vec2::new(10.0, 200.0),
+            }],
+            openings: vec![],
+        }
+    }
+
+    #[test]
+    fn the_slot_path_is_searched_once_and_followed() {
+        let arena = walled_arena();
+        let mut flow_field = FlowField::default();
+        flow_field.rebuild_walls(&arena);
+        let slot = Vec2::new(100.0, 0.0);
+        let mut position = Vec2::new(-100.0, 0.0);
+
+        let mut slot_path = SlotPath::default();
+        let first = slot_path
+            .next_waypoint(position, slot, &flow_field, &arena)
+            .unwrap();
+        let path_length = slot_path.waypoints.as_ref().unwrap().len();
+        assert!(path_length > 10);
+
+        // walking the path only ever drops the waypoints that were reached
+        position = first;
+        let mut reached = 0;
+        while let Some(waypoint) = slot_path.next_waypoint(position, slot, &flow_field, &arena) {
+            reached += 1;
+            assert_eq!(
+                slot_path.waypoints.as_ref().unwrap().len(),
+                path_length - reached
+            );
+            position = waypoint;
+        }
+        assert!(position.distance(slot) < FLOW_FIELD_TILE_SIZE);
+
+        slot_path.clear();
+        assert!(slot_path.waypoints.is_none());
+    }
+
+    #[test]
+    fn a_cell_pushed_behind_a_wall_searches_again() {
+        let arena = walled_arena();
+        let mut flow_field = FlowField::default();
+        flow_field.rebuild_walls(&arena);
+        let slot = Vec2::new(100.0, 0.0);
+
+        let mut slot_path = SlotPath::default();
+        slot_path.next_waypoint(Vec2::new(-100.0, 0.0), slot, &flow_field, &arena);
+        // pushed past the wall, the next waypoint is now on its other side
+        let pushed = Vec2::new(40.0, 100.0);
+        let waypoint = slot_path
+            .next_waypoint(pushed, slot, &flow_field, &arena)
+            .unwrap();
+        assert!(waypoint.distance(pushed) < FLOW_FIELD_TILE_SIZE * 1.5);
+        assert!(arena.is_line_clear(pushed, waypoint, 0.0));
+    }
+}
@@ -0,0 +1,274 @@
+// Infections incubate inside good cells, spread to the cells next to them and are fought
+// off by a strong immune system. Once the infection outgrows the immune value the cell
+// turns, or with some luck recovers stronger.
+// The rules are plain functions of InfectionModel, so they can be tuned and checked without a world
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::core::spatialgrid::CellGrid;
+
+use super::{
+    badcell::BadCellArchetype,
+    cell::CellAttribute,
+    experience::CellExperience,
+    goodcell::{GoodCell, GoodCellClass},
+};
+
+const INFECTED_COLOR: Color = Color::RED;
+
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InfectionModel {
+    // chance in percent that a hit of an ordinary bad cell infects
+    pub hit_chance: i32,
+    pub hit_infection: f32,
+    // spitters infect with every hit
+    pub spitter_infection: f32,
+    // an infection grows by this share of itself every second
+    pub incubation_rate: f32,
+    // and shrinks by this share of the immune value every second
+    pub immune_decay_rate: f32,
+    // share of its infection a cell passes every second to a cell right next to it,
+    // falling off to nothing at spread_radius
+    pub spread_rate: f32,
+    pub spread_radius: f32,
+    // chance in percent that a cell overwhelmed by its infection recovers instead of turning
+    pub golden_recovery_chance: i32,
+    // visible stages on the way from healthy to overwhelmed
+    pub stages: u32,
+}
+
+impl Default for InfectionModel {
+    fn default() -> Self {
+        Self {
+            hit_chance: 5,
+            hit_infection: 2.0,
+            spitter_infection: 2.0,
+            incubation_rate: 0.05,
+            immune_decay_rate: 0.01,
+            spread_rate: 0.05,
+            spread_radius: 40.0,
+            golden_recovery_chance: 30,
+            stages: 3,
+        }
+    }
+}
+
+impl InfectionModel {
+    // Infection a single bad cell hit leaves behind
+    pub fn hit_infection(&self, archetype: BadCellArchetype, rng: &mut impl Rng) -> f32 {
+        if archetype == BadCellArchetype::Spitter {
+            self.spitter_infection
+        } else if rng.gen_range(1..=100) <= self.hit_chance {
+            self.hit_infection
+        } else {
+            0.0
+        }
+    }
+
+    // The infection after delta seconds of incubation against the immune system
+    pub fn incubate(&self, infection: f32, immune: f32, delta: f32) -> f32 {
+        if infection <= 0.0 {
+            return 0.0;
+        }
+        let growth = infection * self.incubation_rate - immune * self.immune_decay_rate;
+        f32::max(0.0, infection + growth * delta)
+    }
+
+    // Infection per second passed to a cell this far away
+    pub fn spread(&self, infection: f32, distance: f32) -> f32 {
+        if infection <= 0.0 || distance >= self.spread_radius {
+            return 0.0;
+        }
+        infection * self.spread_rate * (1.0 - distance / self.spread_radius)
+    }
+
+    pub fn is_overwhelmed(&self, infection: f32, immune: f32) -> bool {
+        infection > immune
+    }
+
+    // 0 is healthy, stages means overwhelmed
+    pub fn stage(&self, infection: f32, immune: f32) -> u32 {
+        if infection <= 0.0 {
+            return 0;
+        }
+        if immune <= 0.0 {
+            return self.stages;
+        }
+        let stage = (infection / immune * self.stages as f32).floor() as u32;
+        u32::min(stage, self.stages)
+    }
+
+    // The base color of a cell, reddened by its infection stage
+    pub fn tint(&self, base: Color, infection: f32, immune: f32) -> Color {
+        if self.stages == 0 {
+            return base;
+        }
+        let progress = self.stage(infection, immune) as f32 / self.stages as f32;
+        let [red, green, blue, alpha] = base.as_rgba_f32();
+        let [infected_red, infected_green, infected_blue, _] = INFECTED_COLOR.as_rgba_f32();
+        Color::rgba(
+            red + (infected_red - red) * progress,
+            green + (infected_green - green) * progress,
+            blue + (infected_blue - blue) * progress,
+            alpha,
+        )
+    }
+}
+
+pub fn incubate_infection(
+    time: Res<Time>,
+    infection_model: Res<InfectionModel>,
+    cell_grid: Res<CellGrid>,
+    mut goodcell_query: Query<
+        (Entity, &Transform, &mut CellAttribute, &CellExperience),
+        With<GoodCell>,
+    >,
+) {
+    let delta = time.delta_seconds();
+
+    // gathered before anything changes, so the result does not depend on the query order
+    let mut spread = vec![];
+    for (good_cell_ent, good_cell_trans, cell_attr, _) in goodcell_query.iter() {
+        if cell_attr.infection <= 0.0 {
+            continue;
+        }
+        for (neighbour_ent, neighbour_pos) in cell_grid
+            .good_cells
+            .query_radius(good_cell_trans.translation, infection_model.spread_radius)
+        {
+            if neighbour_ent == good_cell_ent {
+                continue;
+            }
+            let distance = Vec3::distance(good_cell_trans.translation, neighbour_pos);
+            spread.push((
+                neighbour_ent,
+                infection_model.spread(cell_attr.infection, distance) * delta,
+            ));
+        }
+    }
+
+    for (_, _, mut cell_attr, _) in goodcell_query.iter_mut() {
+        cell_attr.infection =
+            infection_model.incubate(cell_attr.infection, cell_attr.immune, delta);
+    }
+
+    for (neighbour_ent, infection) in spread {
+        if infection <= 0.0 {
+            continue;
+        }
+        if let Ok((_, _, mut cell_attr, experience)) = goodcell_query.get_mut(neighbour_ent) {
+            cell_attr.infect(infection * experience.infection_factor());
+        }
+    }
+}
+
+pub fn tint_infected_cells(
+    infection_model: Res<InfectionModel>,
+    goodcell_query: Query<(&CellAttribute, &GoodCellClass, &Children), With<GoodCell>>,
+    mesh_query: Query<&Handle<ColorMaterial>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    for (cell_attr, class, children) in goodcell_query.iter() {
+        let color = infection_model.tint(class.color(), cell_attr.infection, cell_attr.immune);
+        for child in children.iter() {
+            let Ok(material_handle) = mesh_query.get(*child) else {
+                continue;
+            };
+            // only touch the material when the stage changed
+            if materials
+                .get(material_handle)
+                .is_some_and(|material| material.color != color)
+            {
+                if let Some(material) = materials.get_mut(material_handle) {
+                    material.color = color;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn infection_grows_while_it_outweighs_the_immune_system() {
+        let model = InfectionModel::default();
+        // growth and decay break even where infection * incubation = immune * decay
+        let threshold = 10.0 * model.immune_decay_rate / model.incubation_rate;
+
+        assert!(model.incubate(threshold + 1.0, 10.0, 1.0) > threshold + 1.0);
+        assert!(model.incubate(threshold - 1.0, 10.0, 1.0) < threshold - 1.0);
+        assert_eq!(model.incubate(threshold, 10.0, 1.0), threshold);
+    }
+
+    #[test]
+    fn a_strong_immune_system_clears_the_infection() {
+        let model = InfectionModel::default();
+        let mut infection = 1.0;
+        for _ in 0..100 {
+            infection = model.incubate(infection, 100.0, 1.0);
+        }
+        assert_eq!(infection, 0.0);
+        assert_eq!(model.incubate(0.0, 0.0, 1.0), 0.0);
+    }
+
+    #[test]
+    fn spread_falls_off_with_distance() {
+        let model = InfectionModel::default();
+        let right_next = model.spread(10.0, 0.0);
+
+        assert_eq!(right_next, 10.0 * model.spread_rate);
+        assert_eq!(
+            model.spread(10.0, model.spread_radius / 2.0),
+            right_next / 2.0
+        );
+        assert_eq!(model.spread(10.0, model.spread_radius), 0.0);
+        assert_eq!(model.spread(0.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn stages_follow_the_share_of_the_immune_value() {
+        let model = InfectionModel::default();
+        assert_eq!(model.stage(0.0, 30.0), 0);
+        assert_eq!(model.stage(12.0, 30.0), 1);
+        assert_eq!(model.stage(25.0, 30.0), 2);
+        assert_eq!(model.stage(31.0, 30.0), model.stages);
+        assert_eq!(model.stage(1.0, 0.0), model.stages);
+        assert!(model.is_overwhelmed(31.0, 30.0));
+        assert!(!model.is_overwhelmed(30.0, 30.0));
+    }
+
+    #[test]
+    fn the_tint_reddens_stage_by_stage() {
+        let model = InfectionModel::default();
+        assert_eq!(model.tint(Color::GREEN, 0.0, 30.0), Color::GREEN);
+        assert_eq!(model.tint(Color::GREEN, 31.0, 30.0), INFECTED_COLOR);
+
+        let [red, green, _, _] = model.tint(Color::GREEN, 12.0, 30.0).as_rgba_f32();
+        assert!(red > 0.0 && red < 1.0);
+        assert!(green > 0.0 && green < 1.0);
+    }
+
+    #[test]
+    fn spitters_infect_with_every_hit() {
+        let model = InfectionModel::default();
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..20 {
+            assert_eq!(
+                model.hit_infection(BadCellArchetype::Spitter, &mut rng),
+                model.spitter_infection
+            );
+        }
+
+        let never = InfectionModel {
+            hit_chance: 0,
+            ..default()
+        };
+        assert_eq!(never.hit_infection(BadCellArchetype::Basic, &mut rng), 0.0);
+    }
+}
@@ -0,0 +1,288 @@
+// Lingering effects on a cell during a wave, like poison or a damage shield.
+// Every effect ticks on its own interval until its duration runs out
+use std::fmt;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{cell::CellAttribute, experience::HealthCap};
+
+const DEFAULT_TICK_INTERVAL: f32 = 1.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum StatusEffectKind {
+    // loses magnitude health every tick
+    Poison,
+    // attack cooldowns run magnitude times slower on top of normal, 0.5 is 50% longer
+    Slow,
+    // heals magnitude health every tick
+    Regeneration,
+    // soaks up magnitude damage before the health is hit
+    Shield,
+}
+
+impl StatusEffectKind {
+    // Applying an effect past this many stacks refreshes the stack closest to expiring
+    pub fn max_stacks(&self) -> usize {
+        match self {
+            StatusEffectKind::Poison => 3,
+            StatusEffectKind::Slow => 1,
+            StatusEffectKind::Regeneration => 1,
+            StatusEffectKind::Shield => 1,
+        }
+    }
+}
+
+impl fmt::Display for StatusEffectKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StatusEffectKind::Poison => write!(f, "Poison"),
+            StatusEffectKind::Slow => write!(f, "Slow"),
+            StatusEffectKind::Regeneration => write!(f, "Regeneration"),
+            StatusEffectKind::Shield => write!(f, "Shield"),
+        }
+    }
+}
+
+fn default_tick_interval() -> f32 {
+    DEFAULT_TICK_INTERVAL
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct StatusEffect {
+    pub kind: StatusEffectKind,
+    pub magnitude: f32,
+    // seconds left
+    pub duration: f32,
+    #[serde(default = "default_tick_interval")]
+    pub tick_interval: f32,
+    #[serde(skip)]
+    since_tick: f32,
+}
+
+impl StatusEffect {
+    pub fn new(kind: StatusEffectKind, magnitude: f32, duration: f32) -> Self {
+        Self {
+            kind,
+            magnitude,
+            duration,
+            tick_interval: DEFAULT_TICK_INTERVAL,
+            since_tick: 0.0,
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        self.duration <= 0.0 || (self.kind == StatusEffectKind::Shield && self.magnitude <= 0.0)
+    }
+}
+
+impl fmt::Display for StatusEffect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            StatusEffectKind::Poison | StatusEffectKind::Regeneration => write!(
+                f,
+                "{} {:.1} every {:.1}s ({:.1}s)",
+                self.kind, self.magnitude, self.tick_interval, self.duration
+            ),
+            StatusEffectKind::Slow => write!(
+                f,
+                "{} {:+.0}% cooldown ({:.1}s)",
+                self.kind,
+                self.magnitude * 100.0,
+                self.duration
+            ),
+            StatusEffectKind::Shield => {
+                write!(
+                    f,
+                    "{} {:.0} ({:.1}s)",
+                    self.kind, self.magnitude, self.duration
+                )
+            }
+        }
+    }
+}
+
+// The effects on a cell. Brewed cells keep theirs in the CellBundle until the next wave starts
+#[derive(Component, Clone, Default, Debug, Serialize, Deserialize)]
+pub struct StatusEffects(pub Vec<StatusEffect>);
+
+impl StatusEffects {
+    pub fn apply(&mut self, effect: StatusEffect) {
+        let stacks = self
+            .0
+            .iter()
+            .filter(|active| active.kind == effect.kind)
+            .count();
+        if stacks < effect.kind.max_stacks() {
+            self.0.push(effect);
+            return;
+        }
+
+        if let Some(expiring) = self
+            .0
+            .iter_mut()
+            .filter(|active| active.kind == effect.kind)
+            .min_by(|lhs, rhs| lhs.duration.total_cmp(&rhs.duration))
+        {
+            expiring.duration = f32::max(expiring.duration, effect.duration);
+            expiring.magnitude = f32::max(expiring.magnitude, effect.magnitude);
+        }
+    }
+
+    // How much slower attack cooldowns run
+    pub fn slow_factor(&self) -> f32 {
+        1.0 + self
+            .0
+            .iter()
+            .filter(|active| active.kind == StatusEffectKind::Slow)
+            .map(|active| active.magnitude)
+            .sum::<f32>()
+    }
+
+    // Shields take the damage first, returns what is left for the health
+    pub fn absorb(&mut self, damage: f32) -> f32 {
+        let mut damage = damage;
+        for shield in self
+            .0
+            .iter_mut()
+            .filter(|active| active.kind == StatusEffectKind::Shield)
+        {
+            let absorbed = f32::min(shield.magnitude, damage);
+            shield.magnitude -= absorbed;
+            damage -= absorbed;
+        }
+        self.0.retain(|active| !active.is_expired());
+        damage
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    // Lets delta seconds pass, poison and regeneration act on the cell for every tick
+    // that completes. Regeneration heals up to health_cap
+    pub fn tick(&mut self, delta: f32, cell_attr: &mut CellAttribute, health_cap: f32) {
+        for effect in self.0.iter_mut() {
+            effect.duration -= delta;
+            effect.since_tick += delta;
+            while effect.tick_interval > 0.0 && effect.since_tick >= effect.tick_interval {
+                effect.since_tick -= effect.tick_interval;
+                match effect.kind {
+                    StatusEffectKind::Poison => cell_attr.inflict_dmg(effect.magnitude),
+                    // dead cells stay dead
+                    StatusEffectKind::Regeneration
+                        if cell_attr.health > 0.0 && cell_attr.health < health_cap =>
+                    {
+                        cell_attr.health =
+                            f32::min(cell_attr.health + effect.magnitude, health_cap);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        self.0.retain(|effect| !effect.is_expired());
+    }
+}
+
+impl fmt::Display for StatusEffects {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let effects: Vec<String> = self.0.iter().map(|effect| effect.to_string()).collect();
+        write!(f, "{}", effects.join("\n"))
+    }
+}
+
+pub fn tick_status_effects(
+    time: Res<Time>,
+    mut query: Query<(&mut StatusEffects, &mut CellAttribute, Option<&HealthCap>)>,
+) {
+    for (mut status_effects, mut cell_attr, maybe_health_cap) in query.iter_mut() {
+        if status_effects.is_empty() {
+            continue;
+        }
+
+        let health_cap = maybe_health_cap.map_or(f32::MAX, |health_cap| health_cap.0);
+        status_effects.tick(time.delta_seconds(), &mut cell_attr, health_cap);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::npc::cell::CellAttack;
+
+    fn cell_attribute(health: f32) -> CellAttribute {
+        CellAttribute {
+            health,
+            immune: 10.0,
+            infection: 0.0,
+            cure: 0.0,
+            infection_proof: false,
+            cell_attack: CellAttack::new(1.0, 1.0),
+        }
+    }
+
+    #[test]
+    fn poison_stacks_up_to_its_limit() {
+        let mut status_effects = StatusEffects::default();
+        for duration in [3.0, 1.0, 2.0, 5.0] {
+            status_effects.apply(StatusEffect::new(StatusEffectKind::Poison, 1.0, duration));
+        }
+
+        // the fourth stack refreshed the one closest to expiring
+        let mut durations: Vec<f32> = status_effects
+            .0
+            .iter()
+            .map(|effect| effect.duration)
+            .collect();
+        durations.sort_by(f32::total_cmp);
+        assert_eq!(durations, vec![2.0, 3.0, 5.0]);
+    }
+
+    #[test]
+    fn slows_add_up() {
+        let mut status_effects = StatusEffects::default();
+        assert_eq!(status_effects.slow_factor(), 1.0);
+        status_effects.apply(StatusEffect::new(StatusEffectKind::Slow, 0.5, 3.0));
+        assert_eq!(status_effects.slow_factor(), 1.5);
+    }
+
+    #[test]
+    fn shields_absorb_damage_until_they_break() {
+        let mut status_effects = StatusEffects::default();
+        status_effects.apply(StatusEffect::new(StatusEffectKind::Shield, 10.0, 5.0));
+
+        assert_eq!(status_effects.absorb(4.0), 0.0);
+        assert_eq!(status_effects.absorb(10.0), 4.0);
+        assert!(status_effects.is_empty());
+    }
+
+    #[test]
+    fn poison_ticks_on_its_interval_until_it_runs_out() {
+        let mut status_effects = StatusEffects::default();
+        status_effects.apply(StatusEffect::new(StatusEffectKind::Poison, 2.0, 3.0));
+        let mut cell_attr = cell_attribute(20.0);
+
+        status_effects.tick(0.5, &mut cell_attr, f32::MAX);
+        assert_eq!(cell_attr.health, 20.0);
+        status_effects.tick(0.5, &mut cell_attr, f32::MAX);
+        assert_eq!(cell_attr.health, 18.0);
+        // a long frame runs every tick that fit into it
+        status_effects.tick(2.0, &mut cell_attr, f32::MAX);
+        assert_eq!(cell_attr.health, 14.0);
+        assert!(status_effects.is_empty());
+    }
+
+    #[test]
+    fn regeneration_heals_up_to_the_cap_and_not_the_dead() {
+        let mut status_effects = StatusEffects::default();
+        status_effects.apply(StatusEffect::new(StatusEffectKind::Regeneration, 5.0, 10.0));
+
+        let mut cell_attr = cell_attribute(27.0);
+        status_effects.tick(1.0, &mut cell_attr, 30.0);
+        assert_eq!(cell_attr.health, 30.0);
+
+        let mut dead_attr = cell_attribute(0.0);
+        status_effects.tick(1.0, &mut dead_attr, 30.0);
+        assert_eq!(dead_attr.health, 0.0);
+    }
+}
@@ -4,4 +4,9 @@ pub mod gameover;
 pub mod gameprepare;
 pub mod antidote;
 pub mod playerresource;
-pub mod gamefinish;
\ No newline at end of file
+pub mod gamefinish;
+pub mod wave;
+pub mod savegame;
+pub mod combatlog;
+pub mod wavestats;
+pub mod records;
\ No newline at end of file
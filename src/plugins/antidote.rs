@@ -1,15 +1,33 @@
-use bevy::prelude::*;
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    utils::BoxedFuture,
+};
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    Rng,
+};
+use serde::{Deserialize, Serialize};
 use std::fmt;
+use thiserror::Error;
+
+use crate::npc::{cell::CellBundle, goodcell::GoodCellClass, statuseffect::StatusEffect};
+
+const SUBSTANCE_CATALOG_PATH: &str = "data/base.substances.ron";
 
 pub struct AntidotePlugin;
 
 impl Plugin for AntidotePlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, setup_substances);
+        app.init_asset::<SubstanceCatalog>()
+            .init_asset_loader::<SubstanceCatalogLoader>()
+            .insert_resource(SubstanceResource(vec![]))
+            .add_systems(Startup, setup_substances)
+            .add_systems(Update, sync_substance_resource);
     }
 }
 
-#[derive(Default, Clone)]
+#[derive(Default, Clone, Serialize, Deserialize)]
 pub enum SubstanceType {
     #[default]
     Sweet,
@@ -17,6 +35,19 @@ pub enum SubstanceType {
     Balanced,
 }
 
+impl SubstanceType {
+    // Dropped substances are typed by the sign of their value
+    pub fn from_value(value: f32) -> Self {
+        if value < 0.0 {
+            SubstanceType::Bitter
+        } else if value == 0.0 {
+            SubstanceType::Balanced
+        } else {
+            SubstanceType::Sweet
+        }
+    }
+}
+
 impl fmt::Display for SubstanceType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -27,13 +58,28 @@ impl fmt::Display for SubstanceType {
     }
 }
 
-#[derive(Default, Clone, PartialEq, Eq)]
+#[derive(Default, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum TargetAttribute {
     Attack,
     Speed,
     #[default]
     Immune,
     Health,
+    // lets cells cure weakened bad cells into new recruits
+    Cure,
+}
+
+impl TargetAttribute {
+    // The class of the cells brewed when this target dominates the mix
+    pub fn cell_class(&self) -> GoodCellClass {
+        match self {
+            TargetAttribute::Attack => GoodCellClass::TCell,
+            TargetAttribute::Speed => GoodCellClass::Neutrophil,
+            TargetAttribute::Immune => GoodCellClass::BCell,
+            TargetAttribute::Health => GoodCellClass::Macrophage,
+            TargetAttribute::Cure => GoodCellClass::Naive,
+        }
+    }
 }
 
 impl fmt::Display for TargetAttribute {
@@ -43,48 +89,885 @@ impl fmt::Display for TargetAttribute {
             TargetAttribute::Speed => write!(f, "Attack Cooldown"),
             TargetAttribute::Immune => write!(f, "Immune"),
             TargetAttribute::Health => write!(f, "Health"),
+            TargetAttribute::Cure => write!(f, "Cure"),
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+pub enum SubstanceRarity {
+    #[default]
+    Common,
+    Rare,
+    Epic,
+    Legendary,
+}
+
+impl fmt::Display for SubstanceRarity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubstanceRarity::Common => write!(f, "Common"),
+            SubstanceRarity::Rare => write!(f, "Rare"),
+            SubstanceRarity::Epic => write!(f, "Epic"),
+            SubstanceRarity::Legendary => write!(f, "Legendary"),
         }
     }
 }
 
-#[derive(Component, Default, Clone)] // TODO: or may be a resource?
+// Chance of each tier when a substance drops, legendary ones only come from their own templates
+const RARITY_DROP_WEIGHTS: [(SubstanceRarity, u32); 3] = [
+    (SubstanceRarity::Common, 80),
+    (SubstanceRarity::Rare, 15),
+    (SubstanceRarity::Epic, 5),
+];
+
+impl SubstanceRarity {
+    // How much wider the value range of a template gets at this tier
+    pub fn value_scale(&self) -> f32 {
+        match self {
+            SubstanceRarity::Common => 1.0,
+            SubstanceRarity::Rare => 1.5,
+            SubstanceRarity::Epic => 2.0,
+            // hand-authored, the template range is used as is
+            SubstanceRarity::Legendary => 1.0,
+        }
+    }
+
+    pub fn border_color(&self) -> Color {
+        match self {
+            SubstanceRarity::Common => Color::hex("#8A9490").unwrap(),
+            SubstanceRarity::Rare => Color::hex("#3F8EFC").unwrap(),
+            SubstanceRarity::Epic => Color::hex("#A34FE0").unwrap(),
+            SubstanceRarity::Legendary => Color::hex("#F2B705").unwrap(),
+        }
+    }
+}
+
+// Special effects of the unique legendary reagents, on top of their attribute value
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum ReagentEffect {
+    // the brewed antidote makes every cell immune to infection for good
+    InfectionProof,
+    // the brew creates this many more cells
+    ExtraCells(i32),
+    // the cells start the next wave with this effect
+    Status(StatusEffect),
+}
+
+impl fmt::Display for ReagentEffect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReagentEffect::InfectionProof => write!(f, "Cells can no longer be infected"),
+            ReagentEffect::ExtraCells(count) => write!(f, "Creates {count} extra cells"),
+            ReagentEffect::Status(effect) => write!(f, "Cells start the next wave with {effect}"),
+        }
+    }
+}
+
+#[derive(Component, Default, Clone, Serialize, Deserialize)] // TODO: or may be a resource?
 pub struct Substance {
     pub id: i32,
     pub name: String,
     pub target_attribute: TargetAttribute,
     pub value: f32,
     pub substance_type: SubstanceType,
+    pub icon: String,
+    #[serde(default)]
+    pub rarity: SubstanceRarity,
+    #[serde(default)]
+    pub special: Option<ReagentEffect>,
 }
 
-#[derive(Resource)]
-pub struct SubstanceResource(pub Vec<Substance>);
+// One entry of the substance catalogue, which dropped substances are rolled from
+#[derive(Clone, Deserialize)]
+pub struct SubstanceTemplate {
+    pub name: String,
+    pub target_attribute: TargetAttribute,
+    pub min_value: f32,
+    pub max_value: f32,
+    pub icon: String,
+    pub drop_weight: f32,
+    // the lowest tier this substance drops at
+    #[serde(default)]
+    pub rarity: SubstanceRarity,
+    // only meant for legendary reagents
+    #[serde(default)]
+    pub special: Option<ReagentEffect>,
+}
+
+impl SubstanceTemplate {
+    // Roll the tier of a dropped substance, never below min_rarity,
+    // then its value in the range widened by that tier
+    pub fn roll_substance(
+        &self,
+        id: i32,
+        min_rarity: SubstanceRarity,
+        rng: &mut impl Rng,
+    ) -> Substance {
+        let rarity = if self.rarity == SubstanceRarity::Legendary {
+            self.rarity
+        } else {
+            let rarity_table =
+                WeightedIndex::new(RARITY_DROP_WEIGHTS.iter().map(|(_, weight)| weight)).unwrap();
+            self.rarity
+                .max(min_rarity)
+                .max(RARITY_DROP_WEIGHTS[rarity_table.sample(rng)].0)
+        };
+        let value_scale = rarity.value_scale();
+        let value = rng.gen_range(self.min_value * value_scale..=self.max_value * value_scale);
 
-fn setup_substances(mut commands: Commands) {
-    let substance_resources = vec![
-        Substance {
-            name: "Atagen".to_string(),
-            target_attribute: TargetAttribute::Attack,
-            value: 0.0,
-            ..default()
-        },
         Substance {
-            name: "Speegen".to_string(),
-            target_attribute: TargetAttribute::Speed,
-            value: 0.0,
-            ..default()
+            id,
+            name: self.name.clone(),
+            target_attribute: self.target_attribute.clone(),
+            value,
+            substance_type: SubstanceType::from_value(value),
+            icon: self.icon.clone(),
+            rarity,
+            special: self.special,
+        }
+    }
+}
+
+// Pick the substance a bad cell drops, templates of its loot bias are twice as likely.
+// None when there is nothing to drop
+pub fn roll_drop(
+    templates: &[SubstanceTemplate],
+    loot_bias: Option<TargetAttribute>,
+    id: i32,
+    min_rarity: SubstanceRarity,
+    rng: &mut impl Rng,
+) -> Option<Substance> {
+    let drop_table = WeightedIndex::new(templates.iter().map(|template| {
+        if loot_bias.as_ref() == Some(&template.target_attribute) {
+            template.drop_weight * 2.0
+        } else {
+            template.drop_weight
+        }
+    }))
+    .ok()?;
+    Some(templates[drop_table.sample(rng)].roll_substance(id, min_rarity, rng))
+}
+
+#[derive(Asset, TypePath, Deserialize)]
+pub struct SubstanceCatalog {
+    pub substances: Vec<SubstanceTemplate>,
+}
+
+#[derive(Default)]
+pub struct SubstanceCatalogLoader;
+
+#[derive(Debug, Error)]
+pub enum SubstanceCatalogLoaderError {
+    #[error("Could not read the substance catalogue: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse the substance catalogue: {0}")]
+    RonSpanned(#[from] ron::error::SpannedError),
+}
+
+impl AssetLoader for SubstanceCatalogLoader {
+    type Asset = SubstanceCatalog;
+    type Settings = ();
+    type Error = SubstanceCatalogLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let catalog = parse_substance_catalog(&bytes)?;
+            Ok(catalog)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["substances.ron"]
+    }
+}
+
+pub fn parse_substance_catalog(bytes: &[u8]) -> Result<SubstanceCatalog, ron::error::SpannedError> {
+    ron::de::from_bytes::<SubstanceCatalog>(bytes)
+}
+
+#[derive(Resource)]
+pub struct SubstanceResource(pub Vec<SubstanceTemplate>);
+
+#[derive(Resource)]
+struct SubstanceCatalogHandle(Handle<SubstanceCatalog>);
+
+fn setup_substances(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(SubstanceCatalogHandle(
+        asset_server.load(SUBSTANCE_CATALOG_PATH),
+    ));
+}
+
+// Refresh the droppable substances whenever the catalogue is (re)loaded
+fn sync_substance_resource(
+    mut catalog_events: EventReader<AssetEvent<SubstanceCatalog>>,
+    catalogs: Res<Assets<SubstanceCatalog>>,
+    catalog_handle: Res<SubstanceCatalogHandle>,
+    mut substance_resources: ResMut<SubstanceResource>,
+) {
+    for catalog_event in catalog_events.read() {
+        if !catalog_event.is_loaded_with_dependencies(&catalog_handle.0)
+            && !catalog_event.is_modified(&catalog_handle.0)
+        {
+            continue;
+        }
+
+        if let Some(catalog) = catalogs.get(&catalog_handle.0) {
+            substance_resources.0 = catalog.substances.clone();
+        }
+    }
+}
+
+// The attack cooldown can never be raised by more than this in a single brew
+const MAX_SPEED_GAIN: f32 = 0.2;
+// Every Sweet substance that is not balanced out by a Bitter one adds this much infection
+const EXCESS_SWEET_INFECTION: f32 = 5.0;
+
+// The result of mixing a set of substances in the test tube
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct BrewOutcome {
+    pub new_cell_count: i32,
+    pub attack_gain: f32,
+    pub speed_gain: f32,
+    pub immune_gain: f32,
+    pub health_gain: f32,
+    pub cure_gain: f32,
+    pub infection_gain: f32,
+    pub infection_proof: bool,
+    pub status_effects: Vec<StatusEffect>,
+    pub new_cell_class: GoodCellClass,
+}
+
+impl BrewOutcome {
+    // The same antidote with its attribute changes multiplied by dose,
+    // used when it is only injected into some of the cells.
+    // The infection it adds stays the same, a concentrated dose is not more harmful
+    pub fn scaled(&self, dose: f32) -> BrewOutcome {
+        BrewOutcome {
+            attack_gain: self.attack_gain * dose,
+            speed_gain: f32::min(self.speed_gain * dose, MAX_SPEED_GAIN),
+            immune_gain: self.immune_gain * dose,
+            health_gain: self.health_gain * dose,
+            cure_gain: self.cure_gain * dose,
+            ..self.clone()
+        }
+    }
+
+    pub fn apply_to(&self, cell_bundle: &mut CellBundle) {
+        for effect in self.status_effects.iter() {
+            cell_bundle.status_effects.apply(*effect);
+        }
+
+        let cell_attr = &mut cell_bundle.cell_attribute;
+        cell_attr.cell_attack.damage += self.attack_gain;
+        cell_attr.cell_attack.attack_rate += self.speed_gain;
+        cell_attr.immune += self.immune_gain;
+        cell_attr.health += self.health_gain;
+        cell_attr.cure = f32::max(0.0, cell_attr.cure + self.cure_gain);
+        cell_attr.infection_proof |= self.infection_proof;
+        cell_attr.infect(self.infection_gain);
+    }
+}
+
+// Mix the loaded substances:
+// Sweet + Bitter => create 1 good cell
+// Balanced => create 2 good cells
+// Sweet substances without a Bitter pair make the cells more infected
+// Legendary reagents add their special effect on top
+// The new cells take the class of the dominant substance target
+// Drops are typed by the sign of their value (Sweet above 0, Bitter below), Bitter values are
+// flipped so both types change an attribute the same way, by their size
+pub fn brew(loaded: &[Substance]) -> BrewOutcome {
+    let mut outcome = BrewOutcome::default();
+    let mut total_sweet = 0;
+    let mut total_bitter = 0;
+    let mut total_speed_gain: f32 = 0.0;
+
+    for substance in loaded.iter() {
+        let sweet_factor: f32 = match substance.substance_type {
+            SubstanceType::Balanced => {
+                outcome.new_cell_count += 2;
+                0.0
+            }
+            SubstanceType::Bitter => {
+                total_bitter += 1;
+                -1.0
+            }
+            SubstanceType::Sweet => {
+                total_sweet += 1;
+                1.0
+            }
+        };
+
+        match substance.target_attribute {
+            TargetAttribute::Attack => outcome.attack_gain += substance.value * sweet_factor,
+            TargetAttribute::Speed => total_speed_gain -= substance.value * sweet_factor,
+            TargetAttribute::Immune => outcome.immune_gain -= substance.value * sweet_factor,
+            TargetAttribute::Health => outcome.health_gain += substance.value * sweet_factor,
+            TargetAttribute::Cure => outcome.cure_gain += substance.value * sweet_factor,
+        }
+
+        match substance.special {
+            Some(ReagentEffect::InfectionProof) => outcome.infection_proof = true,
+            Some(ReagentEffect::ExtraCells(count)) => outcome.new_cell_count += count,
+            Some(ReagentEffect::Status(effect)) => outcome.status_effects.push(effect),
+            None => {}
+        }
+    }
+
+    outcome.new_cell_count += i32::min(total_bitter, total_sweet);
+    outcome.speed_gain = f32::min(total_speed_gain, MAX_SPEED_GAIN);
+    outcome.infection_gain = if total_sweet > total_bitter {
+        EXCESS_SWEET_INFECTION * (total_sweet - total_bitter) as f32
+    } else {
+        0.0
+    };
+    outcome.new_cell_class = dominant_cell_class(loaded);
+
+    outcome
+}
+
+// The target with the strongest Sweet and Bitter substances decides the class,
+// a tie or a mix of only Balanced substances makes naive cells
+fn dominant_cell_class(loaded: &[Substance]) -> GoodCellClass {
+    let mut strengths: Vec<(GoodCellClass, f32)> = vec![];
+    for substance in loaded
+        .iter()
+        .filter(|substance| !matches!(substance.substance_type, SubstanceType::Balanced))
+    {
+        let class = substance.target_attribute.cell_class();
+        match strengths.iter_mut().find(|(owned, _)| *owned == class) {
+            Some((_, strength)) => *strength += substance.value.abs(),
+            None => strengths.push((class, substance.value.abs())),
+        }
+    }
+
+    let Some(&(class, strongest)) = strengths
+        .iter()
+        .max_by(|(_, lhs), (_, rhs)| lhs.total_cmp(rhs))
+    else {
+        return GoodCellClass::Naive;
+    };
+    let tied = strengths
+        .iter()
+        .filter(|(_, strength)| *strength == strongest)
+        .count()
+        > 1;
+    if tied {
+        GoodCellClass::Naive
+    } else {
+        class
+    }
+}
+
+// The fusion bench takes this many substances at most
+pub const MAX_FUSION_INPUTS: usize = 3;
+// Every Balanced substance on the bench makes the fused value this much stronger
+const FUSION_CATALYST_BONUS: f32 = 0.25;
+const REFINED_PREFIX: &str = "Refined ";
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum FusionError {
+    #[error("Put 2 or 3 substances on the fusion bench!")]
+    InputCount,
+    #[error("Only substances with the same target can be fused!")]
+    MixedTargets,
+    #[error("Legendary reagents can not be fused!")]
+    Legendary,
+    #[error("Balanced substances need something to refine!")]
+    NothingToRefine,
+}
+
+impl SubstanceRarity {
+    // The tier of a substance fused from three others, legendary stays hand-authored only
+    fn refined(&self) -> Self {
+        match self {
+            SubstanceRarity::Common => SubstanceRarity::Rare,
+            SubstanceRarity::Rare | SubstanceRarity::Epic => SubstanceRarity::Epic,
+            SubstanceRarity::Legendary => SubstanceRarity::Legendary,
+        }
+    }
+}
+
+// Fuse substances of the same target attribute into a single stronger one:
+// Sweet and Bitter values add up, so opposite types cancel each other out
+// Balanced => works as a catalyst, the fused value gets 25% stronger for each
+// Three substances => the result is one rarity tier higher
+pub fn fuse(inputs: &[Substance], id: i32) -> Result<Substance, FusionError> {
+    if !(2..=MAX_FUSION_INPUTS).contains(&inputs.len()) {
+        return Err(FusionError::InputCount);
+    }
+    let first = &inputs[0];
+    if inputs
+        .iter()
+        .any(|substance| substance.target_attribute != first.target_attribute)
+    {
+        return Err(FusionError::MixedTargets);
+    }
+    if inputs
+        .iter()
+        .any(|substance| substance.rarity == SubstanceRarity::Legendary)
+    {
+        return Err(FusionError::Legendary);
+    }
+
+    let mut catalysts = 0;
+    let mut refined_value: Option<f32> = None;
+    for substance in inputs.iter() {
+        match substance.substance_type {
+            SubstanceType::Balanced => catalysts += 1,
+            SubstanceType::Sweet | SubstanceType::Bitter => {
+                *refined_value.get_or_insert(0.0) += substance.value;
+            }
+        }
+    }
+    let catalyst_bonus = 1.0 + FUSION_CATALYST_BONUS * catalysts as f32;
+    let value = refined_value.ok_or(FusionError::NothingToRefine)? * catalyst_bonus;
+
+    let mut rarity = inputs
+        .iter()
+        .map(|substance| substance.rarity)
+        .max()
+        .unwrap_or_default();
+    if inputs.len() == MAX_FUSION_INPUTS {
+        rarity = rarity.refined();
+    }
+
+    let name = if first.name.starts_with(REFINED_PREFIX) {
+        first.name.clone()
+    } else {
+        format!("{REFINED_PREFIX}{}", first.name)
+    };
+
+    Ok(Substance {
+        id,
+        name,
+        target_attribute: first.target_attribute.clone(),
+        value,
+        substance_type: if value < 0.0 {
+            SubstanceType::Bitter
+        } else if value == 0.0 {
+            SubstanceType::Balanced
+        } else {
+            SubstanceType::Sweet
         },
+        icon: first.icon.clone(),
+        rarity,
+        special: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    fn substance(
+        substance_type: SubstanceType,
+        target_attribute: TargetAttribute,
+        value: f32,
+    ) -> Substance {
         Substance {
-            name: "Immugen".to_string(),
-            target_attribute: TargetAttribute::Immune,
-            value: 0.0,
+            substance_type,
+            target_attribute,
+            value,
             ..default()
-        },
+        }
+    }
+
+    #[test]
+    fn sweet_and_bitter_change_an_attribute_by_their_size() {
+        let sweet = brew(&[substance(
+            SubstanceType::Sweet,
+            TargetAttribute::Attack,
+            4.0,
+        )]);
+        assert_eq!(sweet.attack_gain, 4.0);
+
+        let bitter = brew(&[substance(
+            SubstanceType::Bitter,
+            TargetAttribute::Health,
+            -3.0,
+        )]);
+        assert_eq!(bitter.health_gain, 3.0);
+
+        let immune = brew(&[
+            substance(SubstanceType::Sweet, TargetAttribute::Immune, 2.0),
+            substance(SubstanceType::Bitter, TargetAttribute::Immune, -3.0),
+        ]);
+        assert_eq!(immune.immune_gain, -5.0);
+    }
+
+    #[test]
+    fn a_sweet_and_bitter_pair_makes_one_cell() {
+        let outcome = brew(&[
+            substance(SubstanceType::Sweet, TargetAttribute::Attack, 4.0),
+            substance(SubstanceType::Bitter, TargetAttribute::Attack, -1.0),
+        ]);
+        assert_eq!(outcome.new_cell_count, 1);
+        assert_eq!(outcome.attack_gain, 5.0);
+        assert_eq!(outcome.infection_gain, 0.0);
+    }
+
+    #[test]
+    fn balanced_makes_two_cells_without_changing_attributes() {
+        let outcome = brew(&[substance(
+            SubstanceType::Balanced,
+            TargetAttribute::Attack,
+            4.0,
+        )]);
+        assert_eq!(outcome.new_cell_count, 2);
+        assert_eq!(outcome.attack_gain, 0.0);
+        assert_eq!(outcome.new_cell_class, GoodCellClass::Naive);
+    }
+
+    #[test]
+    fn unpaired_sweet_adds_infection() {
+        let outcome = brew(&[
+            substance(SubstanceType::Sweet, TargetAttribute::Health, 1.0),
+            substance(SubstanceType::Sweet, TargetAttribute::Health, 1.0),
+            substance(SubstanceType::Sweet, TargetAttribute::Health, 1.0),
+            substance(SubstanceType::Bitter, TargetAttribute::Health, -1.0),
+        ]);
+        assert_eq!(outcome.new_cell_count, 1);
+        assert_eq!(outcome.infection_gain, 2.0 * EXCESS_SWEET_INFECTION);
+    }
+
+    #[test]
+    fn speed_substances_lower_the_cooldown() {
+        let outcome = brew(&[
+            substance(SubstanceType::Sweet, TargetAttribute::Speed, 0.2),
+            substance(SubstanceType::Bitter, TargetAttribute::Speed, -0.4),
+        ]);
+        assert!((outcome.speed_gain - -0.6).abs() < 1e-6);
+    }
+
+    #[test]
+    fn a_raised_cooldown_is_capped() {
+        // only a substance typed against the sign of its value raises the cooldown,
+        // drops never are, the cap keeps such a brew in check
+        let outcome = brew(&[
+            substance(SubstanceType::Sweet, TargetAttribute::Speed, -0.4),
+            substance(SubstanceType::Sweet, TargetAttribute::Speed, -0.4),
+        ]);
+        assert_eq!(outcome.speed_gain, MAX_SPEED_GAIN);
+    }
+
+    fn template(rarity: SubstanceRarity, min_value: f32, max_value: f32) -> SubstanceTemplate {
+        SubstanceTemplate {
+            name: "Atagen".to_string(),
+            target_attribute: TargetAttribute::Attack,
+            min_value,
+            max_value,
+            icon: String::new(),
+            drop_weight: 1.0,
+            rarity,
+            special: None,
+        }
+    }
+
+    fn base_catalog() -> Vec<SubstanceTemplate> {
+        parse_substance_catalog(include_bytes!("../../assets/data/base.substances.ron"))
+            .unwrap()
+            .substances
+    }
+
+    #[test]
+    fn drops_follow_the_rarity_odds() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let common = template(SubstanceRarity::Common, 1.0, 2.0);
+        let rolls = 20_000;
+        let mut counts = [0; 3];
+        for id in 0..rolls {
+            let substance = common.roll_substance(id, SubstanceRarity::Common, &mut rng);
+            let (tier, max_value) = match substance.rarity {
+                SubstanceRarity::Common => (0, 2.0),
+                SubstanceRarity::Rare => (1, 3.0),
+                SubstanceRarity::Epic => (2, 4.0),
+                SubstanceRarity::Legendary => panic!("only templates drop legendary"),
+            };
+            counts[tier] += 1;
+            assert!(substance.value <= max_value);
+        }
+
+        for (count, (_, weight)) in counts.iter().zip(RARITY_DROP_WEIGHTS.iter()) {
+            let share = *count as f32 / rolls as f32;
+            assert!((share - *weight as f32 / 100.0).abs() < 0.01, "{counts:?}");
+        }
+    }
+
+    #[test]
+    fn drops_never_fall_below_the_minimum_rarity() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let common = template(SubstanceRarity::Common, 1.0, 2.0);
+        let rare = template(SubstanceRarity::Rare, 1.0, 2.0);
+        for id in 0..1_000 {
+            assert!(
+                common
+                    .roll_substance(id, SubstanceRarity::Rare, &mut rng)
+                    .rarity
+                    >= SubstanceRarity::Rare
+            );
+            assert!(
+                rare.roll_substance(id, SubstanceRarity::Common, &mut rng)
+                    .rarity
+                    >= SubstanceRarity::Rare
+            );
+        }
+    }
+
+    #[test]
+    fn legendary_reagents_keep_their_template() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let legendary = SubstanceTemplate {
+            special: Some(ReagentEffect::ExtraCells(3)),
+            ..template(SubstanceRarity::Legendary, -6.0, -3.0)
+        };
+        for id in 0..1_000 {
+            let substance = legendary.roll_substance(id, SubstanceRarity::Common, &mut rng);
+            assert_eq!(substance.rarity, SubstanceRarity::Legendary);
+            assert!((-6.0..=-3.0).contains(&substance.value));
+            assert_eq!(substance.special, Some(ReagentEffect::ExtraCells(3)));
+        }
+    }
+
+    #[test]
+    fn dropped_substances_are_typed_by_their_value() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let bitter = template(SubstanceRarity::Common, -2.0, -1.0).roll_substance(
+            0,
+            SubstanceRarity::Common,
+            &mut rng,
+        );
+        assert!(matches!(bitter.substance_type, SubstanceType::Bitter));
+        let sweet = template(SubstanceRarity::Common, 1.0, 2.0).roll_substance(
+            1,
+            SubstanceRarity::Common,
+            &mut rng,
+        );
+        assert!(matches!(sweet.substance_type, SubstanceType::Sweet));
+        let balanced = template(SubstanceRarity::Common, 0.0, 0.0).roll_substance(
+            2,
+            SubstanceRarity::Common,
+            &mut rng,
+        );
+        assert!(matches!(balanced.substance_type, SubstanceType::Balanced));
+    }
+
+    #[test]
+    fn legendary_reagents_drop_by_their_weight() {
+        let catalog = base_catalog();
+        let total_weight: f32 = catalog.iter().map(|template| template.drop_weight).sum();
+        let legendary_weight: f32 = catalog
+            .iter()
+            .filter(|template| template.rarity == SubstanceRarity::Legendary)
+            .map(|template| template.drop_weight)
+            .sum();
+        assert!(legendary_weight > 0.0);
+
+        let mut rng = StdRng::seed_from_u64(5);
+        let rolls = 20_000;
+        let legendary_drops = (0..rolls)
+            .filter_map(|id| roll_drop(&catalog, None, id, SubstanceRarity::Common, &mut rng))
+            .filter(|substance| substance.rarity == SubstanceRarity::Legendary)
+            .count();
+        let share = legendary_drops as f32 / rolls as f32;
+        assert!((share - legendary_weight / total_weight).abs() < 0.01);
+    }
+
+    #[test]
+    fn the_loot_bias_doubles_a_target() {
+        let catalog = base_catalog();
+        let health_share = |loot_bias: Option<TargetAttribute>| {
+            let mut rng = StdRng::seed_from_u64(6);
+            let rolls = 20_000;
+            let health_drops = (0..rolls)
+                .filter_map(|id| {
+                    roll_drop(
+                        &catalog,
+                        loot_bias.clone(),
+                        id,
+                        SubstanceRarity::Common,
+                        &mut rng,
+                    )
+                })
+                .filter(|substance| substance.target_attribute == TargetAttribute::Health)
+                .count();
+            health_drops as f32 / rolls as f32
+        };
+        assert!(health_share(Some(TargetAttribute::Health)) > health_share(None) * 1.5);
+    }
+
+    #[test]
+    fn nothing_drops_from_an_empty_catalog() {
+        let mut rng = StdRng::seed_from_u64(7);
+        assert!(roll_drop(&[], None, 0, SubstanceRarity::Common, &mut rng).is_none());
+    }
+
+    fn fusion_input(
+        substance_type: SubstanceType,
+        value: f32,
+        rarity: SubstanceRarity,
+    ) -> Substance {
         Substance {
-            name: "Helagen".to_string(),
-            target_attribute: TargetAttribute::Health,
-            value: 0.0,
-            ..default()
-        },
-    ];
-    commands.insert_resource(SubstanceResource(substance_resources));
+            name: "Adrenaline".to_string(),
+            rarity,
+            ..substance(substance_type, TargetAttribute::Attack, value)
+        }
+    }
+
+    #[test]
+    fn fusion_takes_two_or_three_substances() {
+        let input = fusion_input(SubstanceType::Sweet, 1.0, SubstanceRarity::Common);
+        assert_eq!(fuse(&[], 0).err(), Some(FusionError::InputCount));
+        assert_eq!(
+            fuse(std::slice::from_ref(&input), 0).err(),
+            Some(FusionError::InputCount)
+        );
+        assert_eq!(
+            fuse(&vec![input; MAX_FUSION_INPUTS + 1], 0).err(),
+            Some(FusionError::InputCount)
+        );
+    }
+
+    #[test]
+    fn fusion_needs_the_same_target() {
+        let inputs = [
+            fusion_input(SubstanceType::Sweet, 1.0, SubstanceRarity::Common),
+            substance(SubstanceType::Sweet, TargetAttribute::Health, 1.0),
+        ];
+        assert_eq!(fuse(&inputs, 0).err(), Some(FusionError::MixedTargets));
+    }
+
+    #[test]
+    fn legendary_reagents_can_not_be_fused() {
+        let inputs = [
+            fusion_input(SubstanceType::Sweet, 1.0, SubstanceRarity::Common),
+            fusion_input(SubstanceType::Sweet, 1.0, SubstanceRarity::Legendary),
+        ];
+        assert_eq!(fuse(&inputs, 0).err(), Some(FusionError::Legendary));
+    }
+
+    #[test]
+    fn catalysts_alone_have_nothing_to_refine() {
+        let inputs = [
+            fusion_input(SubstanceType::Balanced, 0.0, SubstanceRarity::Common),
+            fusion_input(SubstanceType::Balanced, 0.0, SubstanceRarity::Common),
+        ];
+        assert_eq!(fuse(&inputs, 0).err(), Some(FusionError::NothingToRefine));
+    }
+
+    #[test]
+    fn sweet_and_bitter_cancel_out_into_balanced() {
+        let fused = fuse(
+            &[
+                fusion_input(SubstanceType::Sweet, 3.0, SubstanceRarity::Common),
+                fusion_input(SubstanceType::Bitter, -3.0, SubstanceRarity::Rare),
+            ],
+            7,
+        )
+        .unwrap();
+        assert_eq!(fused.id, 7);
+        assert_eq!(fused.value, 0.0);
+        assert!(matches!(fused.substance_type, SubstanceType::Balanced));
+        // two inputs keep the highest tier
+        assert_eq!(fused.rarity, SubstanceRarity::Rare);
+    }
+
+    #[test]
+    fn a_catalyst_strengthens_the_fused_value() {
+        let fused = fuse(
+            &[
+                fusion_input(SubstanceType::Bitter, -2.0, SubstanceRarity::Common),
+                fusion_input(SubstanceType::Balanced, 0.0, SubstanceRarity::Common),
+            ],
+            0,
+        )
+        .unwrap();
+        assert_eq!(fused.value, -2.0 * (1.0 + FUSION_CATALYST_BONUS));
+        assert!(matches!(fused.substance_type, SubstanceType::Bitter));
+    }
+
+    #[test]
+    fn three_inputs_raise_the_rarity() {
+        let fuse_three = |rarity: SubstanceRarity| {
+            let inputs = [
+                fusion_input(SubstanceType::Sweet, 1.0, SubstanceRarity::Common),
+                fusion_input(SubstanceType::Sweet, 1.0, rarity),
+                fusion_input(SubstanceType::Balanced, 0.0, SubstanceRarity::Common),
+            ];
+            fuse(&inputs, 0).unwrap()
+        };
+        let fused = fuse_three(SubstanceRarity::Common);
+        assert_eq!(fused.rarity, SubstanceRarity::Rare);
+        assert_eq!(fused.value, 2.0 * (1.0 + FUSION_CATALYST_BONUS));
+        assert!(matches!(fused.substance_type, SubstanceType::Sweet));
+        assert_eq!(
+            fuse_three(SubstanceRarity::Rare).rarity,
+            SubstanceRarity::Epic
+        );
+        // epic is the highest tier fusion reaches
+        assert_eq!(
+            fuse_three(SubstanceRarity::Epic).rarity,
+            SubstanceRarity::Epic
+        );
+    }
+
+    #[test]
+    fn fusing_twice_keeps_a_single_prefix() {
+        let input = fusion_input(SubstanceType::Sweet, 1.0, SubstanceRarity::Common);
+        let fused = fuse(&[input.clone(), input.clone()], 0).unwrap();
+        assert_eq!(fused.name, "Refined Adrenaline");
+
+        let fused_again = fuse(&[fused, input], 1).unwrap();
+        assert_eq!(fused_again.name, "Refined Adrenaline");
+    }
+
+    #[test]
+    fn the_strongest_target_decides_the_class() {
+        let outcome = brew(&[
+            substance(SubstanceType::Sweet, TargetAttribute::Attack, 3.0),
+            substance(SubstanceType::Bitter, TargetAttribute::Attack, 2.0),
+            substance(SubstanceType::Sweet, TargetAttribute::Health, 4.0),
+        ]);
+        assert_eq!(outcome.new_cell_class, GoodCellClass::TCell);
+    }
+
+    #[test]
+    fn balanced_substances_do_not_count_towards_the_class() {
+        let outcome = brew(&[
+            substance(SubstanceType::Balanced, TargetAttribute::Attack, 9.0),
+            substance(SubstanceType::Sweet, TargetAttribute::Speed, 0.2),
+            substance(SubstanceType::Bitter, TargetAttribute::Speed, 0.1),
+        ]);
+        assert_eq!(outcome.new_cell_class, GoodCellClass::Neutrophil);
+    }
+
+    #[test]
+    fn a_tie_makes_naive_cells() {
+        let outcome = brew(&[
+            substance(SubstanceType::Sweet, TargetAttribute::Health, 3.0),
+            substance(SubstanceType::Bitter, TargetAttribute::Immune, -3.0),
+        ]);
+        assert_eq!(outcome.new_cell_class, GoodCellClass::Naive);
+    }
+
+    #[test]
+    fn a_scaled_dose_keeps_the_speed_cap() {
+        let outcome = brew(&[substance(
+            SubstanceType::Bitter,
+            TargetAttribute::Speed,
+            0.1,
+        )]);
+        assert_eq!(outcome.scaled(4.0).speed_gain, MAX_SPEED_GAIN);
+        assert_eq!(outcome.scaled(0.5).speed_gain, 0.05);
+    }
 }
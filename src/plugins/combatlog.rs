@@ -0,0 +1,226 @@
+// Gameplay events sent by the combat systems, and a log of them for the current wave.
+// Anything that wants to react to combat (UI, audio, stats) can read these events
+// instead of polling cell components
+use std::time::Duration;
+
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::{
+    core::{simulation::WaveResult, states::GameState},
+    npc::{badcell::BadCellArchetype, cell::CellConversion},
+};
+
+use super::{
+    antidote::{SubstanceRarity, TargetAttribute},
+    playerresource::PlayerResource,
+};
+
+pub struct CombatLogPlugin;
+
+impl Plugin for CombatLogPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<CellDamaged>()
+            .add_event::<CellInfected>()
+            .add_event::<CellRecovered>()
+            .add_event::<CellKilled>()
+            .add_event::<CellConverted>()
+            .add_event::<SubstanceDropped>()
+            .add_event::<WaveEnded>()
+            .init_resource::<CombatLog>()
+            .add_systems(OnEnter(GameState::Game), reset_combat_log)
+            .add_systems(PostUpdate, (record_combat_events, dump_combat_log).chain());
+    }
+}
+
+#[derive(Event, Clone, Debug, Serialize)]
+pub struct CellDamaged {
+    pub entity: Entity,
+    pub attacker: Entity,
+    pub amount: f32,
+    pub health: f32,
+}
+
+// Infection added by an attack
+#[derive(Event, Clone, Debug, Serialize)]
+pub struct CellInfected {
+    pub entity: Entity,
+    pub attacker: Entity,
+    pub amount: f32,
+    pub infection: f32,
+}
+
+// A good cell shrugged off its infection and got stronger instead
+#[derive(Event, Clone, Debug, Serialize)]
+pub struct CellRecovered {
+    pub entity: Entity,
+}
+
+#[derive(Event, Clone, Debug, Serialize)]
+pub struct CellKilled {
+    pub entity: Entity,
+    pub position: Vec3,
+    // set for good cells
+    pub good_cell_id: Option<i32>,
+    // set for bad cells
+    pub archetype: Option<BadCellArchetype>,
+}
+
+#[derive(Event, Clone, Debug, Serialize)]
+pub struct CellConverted {
+    pub entity: Entity,
+    pub conversion: CellConversion,
+}
+
+#[derive(Event, Clone, Debug, Serialize)]
+pub struct SubstanceDropped {
+    pub name: String,
+    pub target_attribute: TargetAttribute,
+    pub value: f32,
+    pub rarity: SubstanceRarity,
+    pub position: Vec3,
+}
+
+#[derive(Event, Clone, Debug, Serialize)]
+pub struct WaveEnded {
+    pub wave_num: i32,
+    pub result: WaveResult,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub enum CombatEvent {
+    CellDamaged(CellDamaged),
+    CellInfected(CellInfected),
+    CellRecovered(CellRecovered),
+    CellKilled(CellKilled),
+    CellConverted(CellConverted),
+    SubstanceDropped(SubstanceDropped),
+    WaveEnded(WaveEnded),
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CombatLogEntry {
+    // seconds since the wave started
+    pub time: f32,
+    pub event: CombatEvent,
+}
+
+#[derive(Resource, Default, Clone, Debug, Serialize)]
+pub struct CombatLog {
+    pub wave_num: i32,
+    pub entries: Vec<CombatLogEntry>,
+    #[serde(skip)]
+    started_at: Duration,
+}
+
+impl CombatLog {
+    pub fn new(wave_num: i32, started_at: Duration) -> Self {
+        Self {
+            wave_num,
+            entries: vec![],
+            started_at,
+        }
+    }
+
+    pub fn record(&mut self, now: Duration, event: CombatEvent) {
+        self.entries.push(CombatLogEntry {
+            time: now.saturating_sub(self.started_at).as_secs_f32(),
+            event,
+        });
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+fn reset_combat_log(
+    time: Res<Time>,
+    player_resources: Res<PlayerResource>,
+    mut combat_log: ResMut<CombatLog>,
+) {
+    *combat_log = CombatLog::new(player_resources.wave_num, time.elapsed());
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn record_combat_events(
+    time: Res<Time>,
+    mut combat_log: ResMut<CombatLog>,
+    mut damaged_events: EventReader<CellDamaged>,
+    mut infected_events: EventReader<CellInfected>,
+    mut recovered_events: EventReader<CellRecovered>,
+    mut killed_events: EventReader<CellKilled>,
+    mut converted_events: EventReader<CellConverted>,
+    mut dropped_events: EventReader<SubstanceDropped>,
+    mut wave_ended_events: EventReader<WaveEnded>,
+) {
+    let now = time.elapsed();
+    let events = damaged_events
+        .read()
+        .cloned()
+        .map(CombatEvent::CellDamaged)
+        .chain(
+            infected_events
+                .read()
+                .cloned()
+                .map(CombatEvent::CellInfected),
+        )
+        .chain(
+            recovered_events
+                .read()
+                .cloned()
+                .map(CombatEvent::CellRecovered),
+        )
+        .chain(killed_events.read().cloned().map(CombatEvent::CellKilled))
+        .chain(
+            converted_events
+                .read()
+                .cloned()
+                .map(CombatEvent::CellConverted),
+        )
+        .chain(
+            dropped_events
+                .read()
+                .cloned()
+                .map(CombatEvent::SubstanceDropped),
+        )
+        .chain(
+            wave_ended_events
+                .read()
+                .cloned()
+                .map(CombatEvent::WaveEnded),
+        );
+
+    for event in events {
+        combat_log.record(now, event);
+    }
+}
+
+// Set ANTIDOTES_COMBAT_LOG to a directory to get one JSON file per finished wave
+#[cfg(not(target_arch = "wasm32"))]
+fn dump_combat_log(mut wave_ended_events: EventReader<WaveEnded>, combat_log: Res<CombatLog>) {
+    let Some(wave_ended) = wave_ended_events.read().last() else {
+        return;
+    };
+    let Some(log_dir) = std::env::var_os("ANTIDOTES_COMBAT_LOG") else {
+        return;
+    };
+
+    let path = std::path::Path::new(&log_dir).join(format!("wave_{}.json", wave_ended.wave_num));
+    let written = combat_log
+        .to_json()
+        .map_err(std::io::Error::from)
+        .and_then(|json| {
+            std::fs::create_dir_all(&log_dir)?;
+            std::fs::write(&path, json)
+        });
+    if let Err(err) = written {
+        warn!(
+            "Could not write the combat log to {}: {err}",
+            path.display()
+        );
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn dump_combat_log() {}
@@ -1,14 +1,22 @@
-use bevy::prelude::*;
+use bevy::{ecs::schedule::SystemConfigs, prelude::*};
 
 use crate::{
-    core::{despawn_entities, states::GameState, userinterface::GAME_THEME_COLOR},
+    core::{
+        arena, despawn_entities, gamerng, navigation, simulation::WaveResult, spatialgrid,
+        states::GameState, userinterface::GAME_THEME_COLOR,
+    },
     npc::{
         badcell::{self, BadCell},
-        cell,
+        boss::{self, Boss},
+        cell::{self, CellAttribute},
+        experience,
         goodcell::{self, GoodCell},
+        infection, statuseffect,
     },
 };
 
+use super::{combatlog::WaveEnded, playerresource::PlayerResource};
+
 pub struct GamePlugin;
 
 impl Plugin for GamePlugin {
@@ -17,19 +25,34 @@ impl Plugin for GamePlugin {
             OnEnter(GameState::Game),
             (
                 setup_game_ui,
+                setup_boss_health_bar,
                 setup_ingame_resources,
-                goodcell::spawn_good_cells,
-                badcell::spawn_bad_cells,
+                (
+                    gamerng::reseed_for_wave,
+                    arena::generate_arena,
+                    goodcell::spawn_good_cells,
+                    badcell::spawn_bad_cells,
+                )
+                    .chain(),
             ),
         )
+        .init_resource::<spatialgrid::CellGrid>()
+        .init_resource::<infection::InfectionModel>()
+        .init_resource::<arena::Arena>()
+        .init_resource::<navigation::FlowField>()
+        .add_event::<cell::CellConversionEvent>()
+        .insert_resource(Time::<Fixed>::from_hz(combat_tick_rate()))
+        .add_systems(
+            FixedUpdate,
+            wave_systems().run_if(in_state(GameState::Game)),
+        )
         .add_systems(
             Update,
             (
-                cell::destroy_cell,
-                cell::track_cell_infection,
-                goodcell::attack,
-                badcell::move_attack,
                 game_loop,
+                infection::tint_infected_cells,
+                boss::draw_boss_aura,
+                update_boss_health_bar,
             )
                 .run_if(in_state(GameState::Game)),
         )
@@ -47,11 +70,50 @@ impl Plugin for GamePlugin {
 #[derive(Component)]
 pub struct OnGameScreen;
 
+const DEFAULT_COMBAT_TICK_RATE: f64 = 60.0;
+
+// Combat runs in FixedUpdate so attack cooldowns do not depend on the frame rate.
+// The rate can be changed with ANTIDOTES_TICK_RATE (in Hz)
+pub fn combat_tick_rate() -> f64 {
+    std::env::var("ANTIDOTES_TICK_RATE")
+        .ok()
+        .and_then(|tick_rate| tick_rate.parse::<f64>().ok())
+        .filter(|tick_rate| *tick_rate > 0.0)
+        .unwrap_or(DEFAULT_COMBAT_TICK_RATE)
+}
+
+// The combat rules of a wave, shared with the headless simulation.
+// Chained so the shared random streams are always drawn in the same order
+pub fn wave_systems() -> SystemConfigs {
+    (
+        spatialgrid::rebuild_cell_grid,
+        navigation::rebuild_flow_field,
+        goodcell::steer,
+        goodcell::attack,
+        badcell::move_attack,
+        boss::update_boss,
+        boss::infection_aura,
+        arena::resolve_obstacle_collisions,
+        experience::regenerate,
+        goodcell::wear_out_neutrophils,
+        statuseffect::tick_status_effects,
+        infection::incubate_infection,
+        cell::track_cell_infection,
+        cell::convert_cells,
+        cell::destroy_cell,
+    )
+        .chain()
+}
+
 #[derive(Resource, Deref, DerefMut)]
 struct GameTimer(Timer);
 
 #[derive(Component)]
 pub struct CollectedSubstanceDisplay(pub i32);
+#[derive(Component)]
+struct BossHealthBar;
+#[derive(Component)]
+struct BossHealthText;
 
 fn setup_ingame_resources(mut commands: Commands) {
     commands.insert_resource(GameTimer(Timer::from_seconds(2.0, TimerMode::Once)));
@@ -76,20 +138,114 @@ fn setup_game_ui(mut commands: Commands) {
     ));
 }
 
+fn setup_boss_health_bar(mut commands: Commands, player_resources: Res<PlayerResource>) {
+    if !boss::is_boss_wave(player_resources.wave_num) {
+        return;
+    }
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(10.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            OnGameScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    "Pathogen Core",
+                    TextStyle {
+                        font_size: 24.0,
+                        color: GAME_THEME_COLOR,
+                        ..default()
+                    },
+                ),
+                BossHealthText,
+            ));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Px(400.0),
+                        height: Val::Px(16.0),
+                        border: UiRect::all(Val::Px(2.0)),
+                        ..default()
+                    },
+                    border_color: GAME_THEME_COLOR.into(),
+                    background_color: Color::BLACK.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Percent(100.0),
+                                height: Val::Percent(100.0),
+                                ..default()
+                            },
+                            background_color: Color::CRIMSON.into(),
+                            ..default()
+                        },
+                        BossHealthBar,
+                    ));
+                });
+        });
+}
+
+fn update_boss_health_bar(
+    boss_query: Query<(&CellAttribute, &Boss)>,
+    mut health_bar_query: Query<&mut Style, With<BossHealthBar>>,
+    mut health_text_query: Query<&mut Text, With<BossHealthText>>,
+) {
+    let (health_ratio, text) = match boss_query.iter().next() {
+        Some((cell_attr, boss)) => (
+            boss.health_ratio(cell_attr),
+            format!("Pathogen Core - {}", boss.phase),
+        ),
+        None => (0.0, "Pathogen Core - Destroyed".to_string()),
+    };
+    for mut style in health_bar_query.iter_mut() {
+        style.width = Val::Percent(health_ratio * 100.0);
+    }
+    for mut health_text in health_text_query.iter_mut() {
+        health_text.sections[0].value = text.clone();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn game_loop(
     time: Res<Time>,
+    player_resources: Res<PlayerResource>,
+    mut wave_ended_events: EventWriter<WaveEnded>,
     goodcell_query: Query<&GoodCell>,
     badcell_query: Query<&BadCell>,
+    boss_query: Query<&Boss>,
     mut game_state: ResMut<NextState<GameState>>,
     mut timer: ResMut<GameTimer>,
 ) {
+    let wave_cleared = boss::is_wave_cleared(
+        player_resources.wave_num,
+        badcell_query.iter().count(),
+        boss_query.iter().count(),
+    );
     if goodcell_query.is_empty() {
         game_state.set(GameState::GameOver);
-    }
-
-    if badcell_query.is_empty() {
-        if timer.tick(time.delta()).finished() {
-            game_state.set(GameState::GameFinish);
-        }
+        wave_ended_events.send(WaveEnded {
+            wave_num: player_resources.wave_num,
+            result: WaveResult::Defeat,
+        });
+    } else if wave_cleared && timer.tick(time.delta()).finished() {
+        game_state.set(GameState::GameFinish);
+        wave_ended_events.send(WaveEnded {
+            wave_num: player_resources.wave_num,
+            result: WaveResult::Victory,
+        });
     }
 }
@@ -6,9 +6,12 @@ use crate::core::{
     userinterface::{GAME_THEME_COLOR, NORMAL_BUTTON},
 };
 use crate::npc::cell::CellAttribute;
+use crate::npc::experience::CellExperience;
 use crate::npc::goodcell::GoodCell;
+use crate::npc::statuseffect::StatusEffects;
 
 use super::playerresource::PlayerResource;
+use super::wavestats::{self, WaveStats};
 
 pub struct GameFinishPlugin;
 
@@ -38,7 +41,7 @@ enum GameFinishButtonAction {
     NextRound,
 }
 
-fn setup_game_finish_screen(mut commands: Commands) {
+fn setup_game_finish_screen(mut commands: Commands, wave_stats: Res<WaveStats>) {
     let button_style = Style {
         width: Val::Px(180.0),
         height: Val::Px(80.0),
@@ -75,7 +78,8 @@ fn setup_game_finish_screen(mut commands: Commands) {
                     style: Style {
                         width: Val::Percent(100.0),
                         height: Val::Percent(85.0),
-                        align_content: AlignContent::Center,
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
                         justify_content: JustifyContent::Center,
                         ..default()
                     },
@@ -90,6 +94,7 @@ fn setup_game_finish_screen(mut commands: Commands) {
                             ..default()
                         },
                     ));
+                    wavestats::spawn_wave_stats(parent, &wave_stats);
                 });
             parent
                 .spawn(NodeBundle {
@@ -137,17 +142,27 @@ fn setup_game_finish_screen(mut commands: Commands) {
         });
 }
 
-fn update_wave_clear(mut player_resources: ResMut<PlayerResource>, good_cell_query: Query<(&CellAttribute, &GoodCell)>) {
+fn update_wave_clear(
+    mut player_resources: ResMut<PlayerResource>,
+    good_cell_query: Query<(&CellAttribute, &CellExperience, &GoodCell)>,
+) {
     player_resources.wave_num += 1;
 
     // Update the fight statistic to player resources
-    for (good_cell_attr, good_cell) in good_cell_query.iter() {
+    for (good_cell_attr, experience, good_cell) in good_cell_query.iter() {
         if let Some(old_cell_bundle) = player_resources.cell_army.get_mut(&good_cell.cell_id) {
             old_cell_bundle.cell_attribute = good_cell_attr.clone();
+            old_cell_bundle.experience = experience.clone();
+            // brewed effects only last for the wave after the brew
+            old_cell_bundle.status_effects = StatusEffects::default();
+            old_cell_bundle
+                .experience
+                .level_up(&mut old_cell_bundle.cell_attribute);
         }
     }
 }
 
+#[allow(clippy::type_complexity)]
 fn game_finish_action(
     interaction_query: Query<
         (&Interaction, &GameFinishButtonAction),
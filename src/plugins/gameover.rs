@@ -4,6 +4,9 @@ use crate::core;
 use crate::core::states::GameState;
 use crate::core::userinterface::{GAME_THEME_COLOR, NORMAL_BUTTON};
 
+use super::playerresource::PlayerResource;
+use super::wavestats::{self, WaveStats};
+
 pub struct GameOverPlugin;
 
 impl Plugin for GameOverPlugin {
@@ -28,7 +31,11 @@ enum GameOverButtonAction {
     ReturnToMainMenu, //TODO: more action coming soon!
 }
 
-fn setup_game_over_screen(mut commands: Commands) {
+fn setup_game_over_screen(
+    mut commands: Commands,
+    player_resources: Res<PlayerResource>,
+    wave_stats: Res<WaveStats>,
+) {
     let button_style = Style {
         width: Val::Px(360.0),
         height: Val::Px(80.0),
@@ -75,7 +82,7 @@ fn setup_game_over_screen(mut commands: Commands) {
                             "You are INFECTED!",
                             TextStyle {
                                 font_size: 70.0,
-                                color: GAME_THEME_COLOR.into(),
+                                color: GAME_THEME_COLOR,
                                 ..default()
                             },
                         )
@@ -98,6 +105,22 @@ fn setup_game_over_screen(mut commands: Commands) {
                             ..default()
                         }),
                     );
+                    parent.spawn(
+                        TextBundle::from_section(
+                            format!("Seed: {}", player_resources.seed),
+                            TextStyle {
+                                font_size: 24.0,
+                                color: Color::rgb(0.7, 0.7, 0.7),
+                                ..default()
+                            },
+                        )
+                        .with_style(Style {
+                            margin: UiRect::all(Val::Px(5.0)),
+                            ..default()
+                        }),
+                    );
+                    wavestats::spawn_wave_stats(parent, &wave_stats);
+                    wavestats::spawn_run_stats(parent, &player_resources.run_stats);
                     parent
                         .spawn((
                             ButtonBundle {
@@ -117,6 +140,7 @@ fn setup_game_over_screen(mut commands: Commands) {
         });
 }
 
+#[allow(clippy::type_complexity)]
 fn game_over_action(
     interaction_query: Query<
         (&Interaction, &GameOverButtonAction),
@@ -1,3 +1,5 @@
+use std::collections::{BTreeMap, BTreeSet};
+
 use bevy::a11y::accesskit::{NodeBuilder, Role};
 use bevy::a11y::AccessibilityNode;
 use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
@@ -6,12 +8,19 @@ use bevy::sprite::MaterialMesh2dBundle;
 use rand::Rng;
 
 use crate::core;
+use crate::core::gamerng::GameRng;
 use crate::core::states::GameState;
 use crate::core::userinterface::{GAME_THEME_COLOR, NORMAL_BUTTON};
-use crate::npc::cell::{CellAttack, CellAttribute, CellBundle};
-use crate::npc::goodcell::{GoodCell, GOOD_CELL_SIZE, GOOD_CELL_SPAWN_RADIUS};
+use crate::npc::cell::CellBundle;
+use crate::npc::experience::{CellExperience, CellPerk};
+use crate::npc::goodcell::{CellStance, GoodCell, GOOD_CELL_SPAWN_RADIUS};
+use crate::npc::infection::InfectionModel;
+use crate::npc::statuseffect::StatusEffects;
 
-use super::antidote::{Substance, SubstanceType, TargetAttribute};
+use super::antidote::{
+    brew, fuse, BrewOutcome, ReagentEffect, Substance, SubstanceType, TargetAttribute,
+    MAX_FUSION_INPUTS,
+};
 use super::playerresource::PlayerResource;
 
 const BITTER_VALUE_COLOR: Color = Color::hsl(12.0, 1.0, 0.75);
@@ -48,10 +57,15 @@ impl Plugin for GamePreparePlugin {
                     create_antidote_btn_action.run_if(in_state(GamePrepareState::CreateAntidote)),
                     redraw_substance_list.run_if(in_state(GamePrepareState::CreateAntidote)),
                     add_to_loaded_substances.run_if(in_state(GamePrepareState::CreateAntidote)),
+                    add_to_fusion_bench.run_if(in_state(GamePrepareState::CreateAntidote)),
                     mouse_scroll.run_if(in_state(GamePrepareState::CreateAntidote)),
                     display_info_message.run_if(in_state(GamePrepareState::CreateAntidote)),
                     animate_test_tube_fill.run_if(in_state(GamePrepareState::CreateAntidote)),
+                    update_brew_preview.run_if(in_state(GamePrepareState::CreateAntidote)),
+                    update_fusion_preview.run_if(in_state(GamePrepareState::CreateAntidote)),
                     drag_hover_cell_arrangement.run_if(in_state(GamePrepareState::CellArrangement)),
+                    cycle_cell_stance.run_if(in_state(GamePrepareState::CellArrangement)),
+                    choose_cell_perk.run_if(in_state(GamePrepareState::CellArrangement)),
                 ),
             )
             .add_systems(
@@ -98,6 +112,7 @@ enum CreateAntidoteButtonAction {
     ReturnToMainMenu,
     UnloadAll,
     Inject,
+    Fuse,
     Go,
 }
 #[derive(Clone, Copy, Default, Eq, PartialEq, Debug, Hash, States)]
@@ -120,17 +135,32 @@ struct InfoMessageUiHolder;
 #[derive(Component)]
 struct TestTubeHolder;
 #[derive(Component)]
+struct BrewPreviewText;
+#[derive(Component)]
+struct FusionBenchText;
+#[derive(Component)]
+struct CellStanceLabel;
+#[derive(Component)]
 enum CellAttributeHover {
     Health,
     Attack,
     Speed,
     Immune,
     Infection,
+    Cure,
+    Class,
+    Level,
+    Perks,
+    Effects,
 }
 
 // Game Prepare Resources
 #[derive(Resource, Deref, DerefMut)]
 struct DisplayTimer(Timer);
+// Ids of the cells selected on the Cell Arrangement screen, the next antidote is only
+// injected into them. Nothing selected means the whole army
+#[derive(Resource, Default, Deref, DerefMut)]
+struct InjectionTargets(BTreeSet<i32>);
 
 fn setup_game_prepare(mut game_prepare_state: ResMut<NextState<GamePrepareState>>) {
     game_prepare_state.set(GamePrepareState::CreateAntidote);
@@ -348,7 +378,7 @@ fn setup_create_antidote_screen(
                             ));
 
                             let player = AnimationPlayer::default();
-                            let anim_tube = Name::new(format!("anim_tube"));
+                            let anim_tube = Name::new("anim_tube".to_string());
 
                             parent
                                 .spawn((
@@ -376,6 +406,73 @@ fn setup_create_antidote_screen(
                                         ..default()
                                     },));
                                 });
+
+                            parent.spawn((
+                                TextBundle::from_section(
+                                    "",
+                                    TextStyle {
+                                        font_size: 22.0,
+                                        color: GAME_THEME_COLOR,
+                                        ..default()
+                                    },
+                                )
+                                .with_style(Style {
+                                    margin: UiRect::top(Val::Px(10.0)),
+                                    ..default()
+                                }),
+                                BrewPreviewText,
+                            ));
+
+                            // the fusion bench
+                            parent
+                                .spawn(NodeBundle {
+                                    style: Style {
+                                        flex_direction: FlexDirection::Row,
+                                        align_items: AlignItems::Center,
+                                        margin: UiRect::top(Val::Px(10.0)),
+                                        ..default()
+                                    },
+                                    ..default()
+                                })
+                                .with_children(|parent| {
+                                    parent.spawn((
+                                        TextBundle::from_section(
+                                            "",
+                                            TextStyle {
+                                                font_size: 20.0,
+                                                color: GAME_THEME_COLOR,
+                                                ..default()
+                                            },
+                                        ),
+                                        FusionBenchText,
+                                    ));
+                                    parent
+                                        .spawn((
+                                            ButtonBundle {
+                                                style: Style {
+                                                    width: Val::Px(120.0),
+                                                    height: Val::Px(40.0),
+                                                    margin: UiRect::left(Val::Px(20.0)),
+                                                    justify_content: JustifyContent::Center,
+                                                    align_items: AlignItems::Center,
+                                                    ..default()
+                                                },
+                                                background_color: NORMAL_BUTTON.into(),
+                                                ..default()
+                                            },
+                                            CreateAntidoteButtonAction::Fuse,
+                                        ))
+                                        .with_children(|parent| {
+                                            parent.spawn(TextBundle::from_section(
+                                                "Fuse",
+                                                TextStyle {
+                                                    font_size: 24.0,
+                                                    color: GAME_THEME_COLOR,
+                                                    ..default()
+                                                },
+                                            ));
+                                        });
+                                });
                         });
                     parent
                         .spawn(NodeBundle {
@@ -502,6 +599,8 @@ fn setup_wave_resource(mut commands: Commands, mut player_resources: ResMut<Play
                     target_attribute: TargetAttribute::Immune,
                     value: 50.0,
                     substance_type: SubstanceType::Balanced,
+                    icon: "sprites/sub_immune.png".to_string(),
+                    ..default()
                 },
             );
             player_resources.substance_id_gen.0 += 1;
@@ -510,12 +609,16 @@ fn setup_wave_resource(mut commands: Commands, mut player_resources: ResMut<Play
     }
 
     commands.insert_resource(DisplayTimer(Timer::from_seconds(4.0, TimerMode::Repeating)));
+    commands.insert_resource(InjectionTargets::default());
 }
 
+#[allow(clippy::too_many_arguments)]
 fn setup_cell_arrangement_screen(
     asset_server: Res<AssetServer>,
     mut commands: Commands,
+    mut game_rng: ResMut<GameRng>,
     mut player_resources: ResMut<PlayerResource>,
+    infection_model: Res<InfectionModel>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     game_prepare_screen_query: Query<Entity, With<OnGamePrepareScreen>>,
@@ -540,24 +643,54 @@ fn setup_cell_arrangement_screen(
     for (id, good_cell_bundle) in player_resources.cell_army.iter_mut() {
         let mut cell_trans = good_cell_bundle.cell_trans;
         if cell_trans == Vec3::ZERO {
-            cell_trans.x =
-                rand::thread_rng().gen_range(-GOOD_CELL_SPAWN_RADIUS..=GOOD_CELL_SPAWN_RADIUS);
-            cell_trans.y =
-                rand::thread_rng().gen_range(-GOOD_CELL_SPAWN_RADIUS..=GOOD_CELL_SPAWN_RADIUS);
+            cell_trans.x = game_rng
+                .spawn
+                .gen_range(-GOOD_CELL_SPAWN_RADIUS..=GOOD_CELL_SPAWN_RADIUS);
+            cell_trans.y = game_rng
+                .spawn
+                .gen_range(-GOOD_CELL_SPAWN_RADIUS..=GOOD_CELL_SPAWN_RADIUS);
         }
-        commands.spawn((
-            MaterialMesh2dBundle {
-                mesh: meshes.add(shape::Circle::new(GOOD_CELL_SIZE).into()).into(),
-                material: materials.add(ColorMaterial::from(Color::GREEN)),
-                transform: Transform::from_translation(cell_trans),
-                ..default()
-            },
-            GoodCell {
-                cell_id: id.clone(),
-                cell_size: GOOD_CELL_SIZE,
-            },
-            OnCellArrangementScreen,
-        ));
+        // infections carry over between waves, so the cells keep their tint
+        let cell_color = infection_model.tint(
+            good_cell_bundle.class.color(),
+            good_cell_bundle.cell_attribute.infection,
+            good_cell_bundle.cell_attribute.immune,
+        );
+        commands
+            .spawn((
+                MaterialMesh2dBundle {
+                    mesh: meshes.add(good_cell_bundle.class.mesh()).into(),
+                    material: materials.add(ColorMaterial::from(cell_color)),
+                    transform: Transform::from_translation(cell_trans),
+                    ..default()
+                },
+                GoodCell {
+                    cell_id: *id,
+                    cell_size: good_cell_bundle.class.size(),
+                },
+                OnCellArrangementScreen,
+            ))
+            .with_children(|parent| {
+                parent.spawn((
+                    Text2dBundle {
+                        text: Text::from_section(
+                            good_cell_bundle.stance.to_string(),
+                            TextStyle {
+                                font_size: 16.0,
+                                color: GAME_THEME_COLOR,
+                                ..default()
+                            },
+                        ),
+                        transform: Transform::from_xyz(
+                            0.0,
+                            -good_cell_bundle.class.size() - 12.0,
+                            1.0,
+                        ),
+                        ..default()
+                    },
+                    CellStanceLabel,
+                ));
+            });
 
         good_cell_bundle.cell_trans = cell_trans;
     }
@@ -831,6 +964,115 @@ fn setup_cell_arrangement_screen(
                         },
                     ));
                 });
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                margin: UiRect::right(Val::Px(10.0)),
+                                align_content: AlignContent::Center,
+                                justify_content: JustifyContent::Center,
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            parent.spawn((
+                                TextBundle::from_section(
+                                    "0",
+                                    TextStyle {
+                                        font_size: 23.0,
+                                        ..default()
+                                    },
+                                ),
+                                CellAttributeHover::Cure,
+                            ));
+                        });
+                    parent.spawn(ImageBundle {
+                        style: Style {
+                            width: Val::Px(35.0),
+                            height: Val::Px(35.0),
+                            ..default()
+                        },
+                        image: UiImage::new(sub_infection_img.clone()),
+                        ..default()
+                    });
+                    parent.spawn(TextBundle::from_section(
+                        "Cure",
+                        TextStyle {
+                            font_size: 20.0,
+                            color: Color::hex("#7ed957").unwrap(),
+                            ..default()
+                        },
+                    ));
+                });
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 20.0,
+                        color: GAME_THEME_COLOR,
+                        ..default()
+                    },
+                ),
+                CellAttributeHover::Class,
+            ));
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 20.0,
+                        color: Color::hex("#f8cc3c").unwrap(),
+                        ..default()
+                    },
+                ),
+                CellAttributeHover::Level,
+            ));
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 18.0,
+                        color: GAME_THEME_COLOR,
+                        ..default()
+                    },
+                ),
+                CellAttributeHover::Perks,
+            ));
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 18.0,
+                        color: SWEET_VALUE_COLOR,
+                        ..default()
+                    },
+                ),
+                CellAttributeHover::Effects,
+            ));
+            parent.spawn(TextBundle::from_section(
+                "Right click to change the stance",
+                TextStyle {
+                    font_size: 18.0,
+                    color: GAME_THEME_COLOR,
+                    ..default()
+                },
+            ));
+            parent.spawn(TextBundle::from_section(
+                "Ctrl + click to select it for the next injection",
+                TextStyle {
+                    font_size: 18.0,
+                    color: GAME_THEME_COLOR,
+                    ..default()
+                },
+            ));
         })
         .id();
 
@@ -840,8 +1082,11 @@ fn setup_cell_arrangement_screen(
         .add_child(cell_attr_card_ent);
 }
 
+#[allow(clippy::too_many_arguments)]
 fn drag_hover_cell_arrangement(
     mouse_buttons: Res<Input<MouseButton>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut injection_targets: ResMut<InjectionTargets>,
     camera_query: Query<(&Camera, &GlobalTransform)>,
     windows: Query<&Window>,
     mut cell_attribute_hover_query: Query<(&mut Text, &CellAttributeHover)>,
@@ -860,11 +1105,39 @@ fn drag_hover_cell_arrangement(
         return;
     };
 
+    let selecting = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
     let mut cell_attr_hover_panel = cell_attr_hover_panel_query.single_mut();
     let mut cursor_hit_cell = false;
     for (good_cell, mut cell_trans) in cell_arrangement_query.iter_mut() {
+        // cells that levelled up and still wait for a perk
+        if player_resources
+            .cell_army
+            .get(&good_cell.cell_id)
+            .is_some_and(|cell_bundle| cell_bundle.experience.perk_choices > 0)
+        {
+            gizmos.circle_2d(
+                cell_trans.translation.truncate(),
+                good_cell.cell_size + 8.0,
+                Color::hex("#f8cc3c").unwrap(),
+            );
+        }
+
+        if injection_targets.contains(&good_cell.cell_id) {
+            gizmos.circle_2d(
+                cell_trans.translation.truncate(),
+                good_cell.cell_size + 4.0,
+                GAME_THEME_COLOR,
+            );
+        }
+
         if Vec2::distance(cell_trans.translation.truncate(), point) <= good_cell.cell_size {
-            if mouse_buttons.pressed(MouseButton::Left) {
+            if selecting {
+                if mouse_buttons.just_pressed(MouseButton::Left)
+                    && !injection_targets.remove(&good_cell.cell_id)
+                {
+                    injection_targets.insert(good_cell.cell_id);
+                }
+            } else if mouse_buttons.pressed(MouseButton::Left) {
                 cell_trans.translation = point.extend(0.0);
             }
 
@@ -892,6 +1165,27 @@ fn drag_hover_cell_arrangement(
                             text_val.value =
                                 format!("{:.2}", good_cell_attr.cell_attribute.infection)
                         }
+                        CellAttributeHover::Cure => {
+                            text_val.value = format!("{:.2}", good_cell_attr.cell_attribute.cure)
+                        }
+                        CellAttributeHover::Class => {
+                            text_val.value = good_cell_attr.class.to_string()
+                        }
+                        CellAttributeHover::Level => {
+                            let experience = &good_cell_attr.experience;
+                            text_val.value = format!(
+                                "Level {} ({:.0}/{:.0} XP)",
+                                experience.level,
+                                experience.xp,
+                                experience.xp_to_next_level()
+                            )
+                        }
+                        CellAttributeHover::Perks => {
+                            text_val.value = perks_text(&good_cell_attr.experience)
+                        }
+                        CellAttributeHover::Effects => {
+                            text_val.value = good_cell_attr.status_effects.to_string()
+                        }
                     }
                 }
             }
@@ -907,6 +1201,111 @@ fn drag_hover_cell_arrangement(
     gizmos.circle_2d(point, 5.0, Color::WHITE);
 }
 
+// Right clicking a cell cycles through the stances it will fight with
+fn cycle_cell_stance(
+    mouse_buttons: Res<Input<MouseButton>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    windows: Query<&Window>,
+    cell_arrangement_query: Query<(&GoodCell, &Transform, &Children)>,
+    mut cell_stance_label_query: Query<&mut Text, With<CellStanceLabel>>,
+    mut player_resources: ResMut<PlayerResource>,
+) {
+    if !mouse_buttons.just_pressed(MouseButton::Right) {
+        return;
+    }
+
+    let (camera, camera_transform) = camera_query.single();
+    let Some(cursor_position) = windows.single().cursor_position() else {
+        return;
+    };
+    let Some(point) = camera.viewport_to_world_2d(camera_transform, cursor_position) else {
+        return;
+    };
+
+    for (good_cell, cell_trans, children) in cell_arrangement_query.iter() {
+        if Vec2::distance(cell_trans.translation.truncate(), point) > good_cell.cell_size {
+            continue;
+        }
+
+        if let Some(cell_bundle) = player_resources.cell_army.get_mut(&good_cell.cell_id) {
+            cell_bundle.stance = cell_bundle.stance.next();
+
+            let mut stance_labels = cell_stance_label_query.iter_many_mut(children);
+            while let Some(mut text) = stance_labels.fetch_next() {
+                text.sections[0].value = cell_bundle.stance.to_string();
+            }
+        }
+
+        // only the first cell under the cursor
+        break;
+    }
+}
+
+fn perks_text(experience: &CellExperience) -> String {
+    if experience.perk_choices > 0 {
+        let perk_keys: Vec<String> = CellPerk::ALL
+            .iter()
+            .enumerate()
+            .map(|(idx, perk)| format!("{} {perk}", idx + 1))
+            .collect();
+        return format!("Level up! Press {}", perk_keys.join(", "));
+    }
+    if experience.perks.is_empty() {
+        return "No perks yet".to_string();
+    }
+
+    let owned_perks: Vec<String> = CellPerk::ALL
+        .iter()
+        .filter_map(|perk| match experience.perk_rank(*perk) {
+            0 => None,
+            1 => Some(perk.to_string()),
+            rank => Some(format!("{perk} x{rank}")),
+        })
+        .collect();
+    format!("Perks: {}", owned_perks.join(", "))
+}
+
+// Pressing 1 to 4 over a cell that levelled up picks its perk
+fn choose_cell_perk(
+    keyboard_input: Res<Input<KeyCode>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    windows: Query<&Window>,
+    cell_arrangement_query: Query<(&GoodCell, &Transform)>,
+    mut player_resources: ResMut<PlayerResource>,
+) {
+    let perk_keys = [KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4];
+    let Some(perk) = perk_keys
+        .iter()
+        .zip(CellPerk::ALL)
+        .find(|(key, _)| keyboard_input.just_pressed(**key))
+        .map(|(_, perk)| perk)
+    else {
+        return;
+    };
+
+    let (camera, camera_transform) = camera_query.single();
+    let Some(cursor_position) = windows.single().cursor_position() else {
+        return;
+    };
+    let Some(point) = camera.viewport_to_world_2d(camera_transform, cursor_position) else {
+        return;
+    };
+
+    for (good_cell, cell_trans) in cell_arrangement_query.iter() {
+        if Vec2::distance(cell_trans.translation.truncate(), point) > good_cell.cell_size {
+            continue;
+        }
+
+        if let Some(cell_bundle) = player_resources.cell_army.get_mut(&good_cell.cell_id) {
+            cell_bundle.experience.choose_perk(perk);
+        }
+
+        // only the first cell under the cursor
+        break;
+    }
+}
+
+#[allow(clippy::type_complexity)]
 fn game_prepare_btn_action(
     mut interaction_query: Query<
         (&Interaction, &GamePrepareButtonAction),
@@ -947,6 +1346,7 @@ fn game_prepare_btn_action(
     }
 }
 
+#[allow(clippy::type_complexity)]
 fn create_antidote_btn_action(
     interaction_query: Query<
         (&Interaction, &CreateAntidoteButtonAction),
@@ -957,6 +1357,7 @@ fn create_antidote_btn_action(
     mut game_prepare_state: ResMut<NextState<GamePrepareState>>,
     mut send_info_message_events: EventWriter<InfoMessageEvent>,
     mut redraw_events: EventWriter<RedrawSubstanceListEvent>,
+    mut injection_targets: ResMut<InjectionTargets>,
 ) {
     for (interaction, create_antidote_button_action) in &interaction_query {
         if *interaction == Interaction::Pressed {
@@ -984,8 +1385,34 @@ fn create_antidote_btn_action(
                         .substance_collection
                         .extend(temp_loaded_substances);
                     player_resources.loaded_substances.clear();
+                    let temp_fusion_bench = std::mem::take(&mut player_resources.fusion_bench);
+                    player_resources
+                        .substance_collection
+                        .extend(temp_fusion_bench);
                     redraw_events.send_default();
                 }
+                CreateAntidoteButtonAction::Fuse => {
+                    let inputs: Vec<Substance> =
+                        player_resources.fusion_bench.values().cloned().collect();
+                    match fuse(&inputs, player_resources.substance_id_gen.0) {
+                        Ok(fused_substance) => {
+                            send_info_message_events.send(InfoMessageEvent(
+                                format!("Fused into {}!", fused_substance.name),
+                                GAME_THEME_COLOR,
+                            ));
+                            player_resources.substance_id_gen.0 += 1;
+                            player_resources.fusion_bench.clear();
+                            player_resources
+                                .substance_collection
+                                .insert(fused_substance.id, fused_substance);
+                            redraw_events.send_default();
+                        }
+                        Err(err) => {
+                            send_info_message_events
+                                .send(InfoMessageEvent(err.to_string(), Color::RED));
+                        }
+                    }
+                }
                 CreateAntidoteButtonAction::Inject => {
                     if player_resources.loaded_substances.is_empty() {
                         send_info_message_events.send(InfoMessageEvent(
@@ -994,80 +1421,37 @@ fn create_antidote_btn_action(
                         ));
                         return;
                     }
-                    // now the fun part:
-                    // Sweet + Bitter => create 1 good cell
-                    // Balanced => create 2 good cells
-                    let mut to_spawn_cell_count = 0;
-                    let mut total_sweet = 0;
-                    let mut total_bitter = 0;
-                    let mut total_attack_gain: f32 = 0.0;
-                    let mut total_speed_gain: f32 = 0.0;
-                    let mut total_immu_gain: f32 = 0.0;
-                    let mut total_health_gain: f32 = 0.0;
-                    for (_, substance) in player_resources.loaded_substances.iter() {
-                        let sweet_factor: f32;
-                        match substance.substance_type {
-                            SubstanceType::Balanced => {
-                                to_spawn_cell_count += 2;
-                                sweet_factor = 0.0;
-                            }
-                            SubstanceType::Bitter => {
-                                total_bitter += 1;
-                                sweet_factor = -1.0;
-                            }
-                            SubstanceType::Sweet => {
-                                total_sweet += 1;
-                                sweet_factor = 1.0;
-                            }
-                        };
-
-                        match substance.target_attribute {
-                            TargetAttribute::Attack => {
-                                total_attack_gain += substance.value * sweet_factor
-                            }
-                            TargetAttribute::Speed => {
-                                total_speed_gain -= substance.value * sweet_factor
-                            }
-                            TargetAttribute::Immune => {
-                                total_immu_gain -= substance.value * sweet_factor
-                            }
-                            TargetAttribute::Health => {
-                                total_health_gain += substance.value * sweet_factor
-                            }
-                        }
-                    }
+                    let loaded: Vec<Substance> = player_resources
+                        .loaded_substances
+                        .values()
+                        .cloned()
+                        .collect();
+                    let injection =
+                        plan_injection(&loaded, &player_resources.cell_army, &injection_targets);
+                    let outcome = &injection.outcome;
+                    player_resources.run_stats.substances_brewed += loaded.len() as i32;
 
-                    to_spawn_cell_count += i32::min(total_bitter, total_sweet);
-                    let total_add_infection: f32 = if total_sweet > total_bitter {
-                        5.0 * (total_sweet - total_bitter) as f32
-                    } else {
-                        0.0
-                    };
-
-                    // apply modified attribute to all cell
-                    for (_, each_cell_bundle) in player_resources.cell_army.iter_mut() {
-                        each_cell_bundle.cell_attribute.cell_attack.damage += total_attack_gain;
-                        each_cell_bundle.cell_attribute.cell_attack.attack_rate +=
-                            f32::min(total_speed_gain, 0.2);
-                        each_cell_bundle.cell_attribute.immune += total_immu_gain;
-                        each_cell_bundle.cell_attribute.health += total_health_gain;
-                        each_cell_bundle.cell_attribute.infection += total_add_infection;
+                    // apply modified attribute to the selected cells, or to all cells
+                    for cell_id in injection.cell_ids.iter() {
+                        if let Some(cell_bundle) = player_resources.cell_army.get_mut(cell_id) {
+                            outcome.apply_to(cell_bundle);
+                        }
                     }
+                    injection_targets.clear();
 
                     // spawn new cells
                     let mut counter = 0;
                     let mut cell_id = player_resources.good_cell_id.0;
-                    while counter < to_spawn_cell_count {
+                    while counter < outcome.new_cell_count {
                         player_resources.cell_army.insert(
                             cell_id,
                             CellBundle {
                                 cell_trans: Vec3::ZERO,
-                                cell_attribute: CellAttribute {
-                                    health: 50.0,
-                                    cell_attack: CellAttack::new(0.5, 20.0),
-                                    immune: 30.0,
-                                    infection: 0.0,
-                                },
+                                cell_attribute: outcome.new_cell_class.recruit_attribute(),
+                                stance: CellStance::default(),
+                                experience: CellExperience::default(),
+                                class: outcome.new_cell_class,
+                                status_effects: StatusEffects::default(),
                             },
                         );
                         counter += 1;
@@ -1075,6 +1459,9 @@ fn create_antidote_btn_action(
                     }
                     player_resources.good_cell_id.0 = cell_id;
                     player_resources.loaded_substances.clear();
+                    let army_size = player_resources.cell_army.len();
+                    player_resources.run_stats.note_army_size(army_size);
+                    redraw_events.send_default();
 
                     if counter > 0 {
                         send_info_message_events.send(InfoMessageEvent(
@@ -1088,6 +1475,7 @@ fn create_antidote_btn_action(
     }
 }
 
+#[allow(clippy::type_complexity)]
 fn add_to_loaded_substances(
     mut interaction_query: Query<
         (&Interaction, &GamePrepareSubstanceCard),
@@ -1099,29 +1487,65 @@ fn add_to_loaded_substances(
     mut send_animate_test_tube_events: EventWriter<AnimateTestTubeEvent>,
 ) {
     for (interaction, substance_card) in &mut interaction_query {
-        match *interaction {
-            Interaction::Pressed => {
-                if player_resources.loaded_substances.len() >= 6 {
-                    send_info_message_events.send(InfoMessageEvent(
-                        "Can not put more than 6 substances to create an antidote!".to_string(),
-                        Color::RED,
-                    ));
-                    continue;
-                }
-
-                if let Some(loaded_substance) = player_resources
-                    .substance_collection
-                    .remove(&substance_card.0)
-                {
-                    player_resources
-                        .loaded_substances
-                        .insert(loaded_substance.id, loaded_substance);
-                    redraw_events.send_default();
-                }
+        if *interaction == Interaction::Pressed {
+            if player_resources.loaded_substances.len() >= 6 {
+                send_info_message_events.send(InfoMessageEvent(
+                    "Can not put more than 6 substances to create an antidote!".to_string(),
+                    Color::RED,
+                ));
+                continue;
+            }
 
-                send_animate_test_tube_events.send_default();
+            if let Some(loaded_substance) = player_resources
+                .substance_collection
+                .remove(&substance_card.0)
+            {
+                player_resources
+                    .loaded_substances
+                    .insert(loaded_substance.id, loaded_substance);
+                redraw_events.send_default();
             }
-            _ => (),
+
+            send_animate_test_tube_events.send_default();
+        }
+    }
+}
+
+// Right click puts a substance on the fusion bench instead of into the test tube
+fn add_to_fusion_bench(
+    interaction_query: Query<(&Interaction, &GamePrepareSubstanceCard), With<Button>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    mut player_resources: ResMut<PlayerResource>,
+    mut redraw_events: EventWriter<RedrawSubstanceListEvent>,
+    mut send_info_message_events: EventWriter<InfoMessageEvent>,
+) {
+    if !mouse_buttons.just_pressed(MouseButton::Right) {
+        return;
+    }
+
+    for (interaction, substance_card) in &interaction_query {
+        if *interaction != Interaction::Hovered {
+            continue;
+        }
+
+        if player_resources.fusion_bench.len() >= MAX_FUSION_INPUTS {
+            send_info_message_events.send(InfoMessageEvent(
+                format!(
+                    "Can not put more than {MAX_FUSION_INPUTS} substances on the fusion bench!"
+                ),
+                Color::RED,
+            ));
+            continue;
+        }
+
+        if let Some(bench_substance) = player_resources
+            .substance_collection
+            .remove(&substance_card.0)
+        {
+            player_resources
+                .fusion_bench
+                .insert(bench_substance.id, bench_substance);
+            redraw_events.send_default();
         }
     }
 }
@@ -1158,19 +1582,10 @@ fn redraw_substance_list(
     player_resources: Res<PlayerResource>,
 ) {
     if !redraw_events.is_empty() {
-        let sub_attack_img: Handle<Image> = asset_server.load("sprites/sub_attack.png");
-        let sub_speed_img: Handle<Image> = asset_server.load("sprites/sub_speed.png");
-        let sub_immune_img: Handle<Image> = asset_server.load("sprites/sub_immune.png");
-        let sub_health_img: Handle<Image> = asset_server.load("sprites/sub_health.png");
         let mut spawned_card_ents = vec![];
 
         for (substance_id, substance) in player_resources.substance_collection.iter() {
-            let substance_info_img = match substance.target_attribute {
-                TargetAttribute::Attack => &sub_attack_img,
-                TargetAttribute::Speed => &sub_speed_img,
-                TargetAttribute::Health => &sub_health_img,
-                TargetAttribute::Immune => &sub_immune_img,
-            };
+            let substance_info_img: Handle<Image> = asset_server.load(&substance.icon);
 
             let spawned_card_ent = commands
                 .spawn((
@@ -1179,9 +1594,11 @@ fn redraw_substance_list(
                             width: Val::Percent(80.0),
                             height: Val::Px(100.0),
                             margin: UiRect::all(Val::Px(10.0)),
+                            border: UiRect::all(Val::Px(3.0)),
                             ..default()
                         },
                         background_color: Color::hex("#5D6965").unwrap().into(),
+                        border_color: substance.rarity.border_color().into(),
                         ..default()
                     },
                     AccessibilityNode(NodeBuilder::new(Role::ListItem)),
@@ -1218,11 +1635,11 @@ fn redraw_substance_list(
                                                 height: Val::Percent(100.0),
                                                 ..default()
                                             },
-                                            image: UiImage::new(substance_info_img.clone()),
+                                            image: UiImage::new(substance_info_img),
                                             background_color: NORMAL_BUTTON.into(),
                                             ..default()
                                         },
-                                        GamePrepareSubstanceCard(substance_id.clone()),
+                                        GamePrepareSubstanceCard(*substance_id),
                                     ));
                                 });
 
@@ -1268,7 +1685,7 @@ fn redraw_substance_list(
                                                     parent.spawn(TextBundle::from_section(
                                                         substance.name.clone(),
                                                         TextStyle {
-                                                            color: Color::WHITE.into(),
+                                                            color: Color::WHITE,
                                                             font_size: 16.0,
                                                             ..default()
                                                         },
@@ -1289,9 +1706,7 @@ fn redraw_substance_list(
                                                     parent.spawn(TextBundle::from_section(
                                                         substance.substance_type.to_string(),
                                                         TextStyle {
-                                                            color: Color::hex("#6CA894")
-                                                                .unwrap()
-                                                                .into(),
+                                                            color: Color::hex("#6CA894").unwrap(),
                                                             font_size: 16.0,
                                                             ..default()
                                                         },
@@ -1310,35 +1725,35 @@ fn redraw_substance_list(
                                             ..default()
                                         })
                                         .with_children(|parent| {
-                                            let (value_txt, display_color) =
-                                                match substance.substance_type {
-                                                    SubstanceType::Bitter => (
-                                                        format!(
-                                                            "{:.2} {}",
-                                                            substance.value,
-                                                            substance.target_attribute.to_string()
-                                                        ),
-                                                        BITTER_VALUE_COLOR,
+                                            let (value_txt, display_color) = match substance
+                                                .substance_type
+                                            {
+                                                SubstanceType::Bitter => (
+                                                    format!(
+                                                        "{:.2} {}",
+                                                        substance.value, substance.target_attribute
                                                     ),
-                                                    SubstanceType::Sweet => (
-                                                        format!(
-                                                            "+{:.2} {}",
-                                                            substance.value,
-                                                            substance.target_attribute.to_string()
-                                                        ),
-                                                        SWEET_VALUE_COLOR,
+                                                    BITTER_VALUE_COLOR,
+                                                ),
+                                                SubstanceType::Sweet => (
+                                                    format!(
+                                                        "+{:.2} {}",
+                                                        substance.value, substance.target_attribute
                                                     ),
-                                                    SubstanceType::Balanced => (
-                                                        "Create 2 new cells".to_string(),
-                                                        SWEET_VALUE_COLOR,
-                                                    ),
-                                                };
+                                                    SWEET_VALUE_COLOR,
+                                                ),
+                                                SubstanceType::Balanced => (
+                                                    "Create 2 new cells".to_string(),
+                                                    SWEET_VALUE_COLOR,
+                                                ),
+                                            };
                                             parent
                                                 .spawn(NodeBundle {
                                                     style: Style {
                                                         width: Val::Percent(100.0),
                                                         height: Val::Percent(100.0),
-                                                        align_content: AlignContent::Center,
+                                                        flex_direction: FlexDirection::Column,
+                                                        align_items: AlignItems::Center,
                                                         justify_content: JustifyContent::Center,
                                                         margin: UiRect::top(Val::Px(28.0)),
                                                         ..default()
@@ -1354,6 +1769,18 @@ fn redraw_substance_list(
                                                             ..default()
                                                         },
                                                     ));
+                                                    if let Some(special) = substance.special {
+                                                        parent.spawn(TextBundle::from_section(
+                                                            special.to_string(),
+                                                            TextStyle {
+                                                                font_size: 14.0,
+                                                                color: substance
+                                                                    .rarity
+                                                                    .border_color(),
+                                                                ..default()
+                                                            },
+                                                        ));
+                                                    }
                                                 });
                                         });
                                 });
@@ -1371,6 +1798,209 @@ fn redraw_substance_list(
     }
 }
 
+// Forecast what the loaded substances will do, using the same rules as the Inject button
+fn update_brew_preview(
+    mut redraw_events: EventReader<RedrawSubstanceListEvent>,
+    mut preview_text_query: Query<&mut Text, With<BrewPreviewText>>,
+    player_resources: Res<PlayerResource>,
+    injection_targets: Res<InjectionTargets>,
+) {
+    if redraw_events.is_empty() {
+        return;
+    }
+    redraw_events.clear();
+
+    let Ok(mut preview_text) = preview_text_query.get_single_mut() else {
+        return;
+    };
+
+    let text_style = TextStyle {
+        font_size: 22.0,
+        color: GAME_THEME_COLOR,
+        ..default()
+    };
+
+    if player_resources.loaded_substances.is_empty() {
+        preview_text.sections = vec![TextSection::new(
+            "Load substances to see the antidote effect",
+            text_style,
+        )];
+        return;
+    }
+
+    let loaded: Vec<Substance> = player_resources
+        .loaded_substances
+        .values()
+        .cloned()
+        .collect();
+    let injection = plan_injection(&loaded, &player_resources.cell_army, &injection_targets);
+    let outcome = &injection.outcome;
+
+    let new_cells = if outcome.new_cell_count > 0 {
+        format!(
+            "New cells: {} ({})\n",
+            outcome.new_cell_count, outcome.new_cell_class
+        )
+    } else {
+        format!("New cells: {}\n", outcome.new_cell_count)
+    };
+    let mut sections = vec![TextSection::new(new_cells, text_style.clone())];
+
+    // a lower attack cooldown is a buff, so its colors are flipped
+    let attribute_changes = [
+        ("Damage", outcome.attack_gain, false),
+        ("Attack Cooldown", outcome.speed_gain, true),
+        ("Immune", outcome.immune_gain, false),
+        ("Health", outcome.health_gain, false),
+        ("Cure", outcome.cure_gain, false),
+    ];
+    for (attribute_name, gain, lower_is_better) in attribute_changes {
+        if gain == 0.0 {
+            continue;
+        }
+
+        let is_buff = (gain > 0.0) != lower_is_better;
+        sections.push(TextSection::new(
+            format!("{attribute_name} {gain:+.2}\n"),
+            TextStyle {
+                color: if is_buff {
+                    SWEET_VALUE_COLOR
+                } else {
+                    BITTER_VALUE_COLOR
+                },
+                ..text_style.clone()
+            },
+        ));
+    }
+
+    if outcome.infection_proof {
+        sections.push(TextSection::new(
+            format!("{}\n", ReagentEffect::InfectionProof),
+            TextStyle {
+                color: SWEET_VALUE_COLOR,
+                ..text_style.clone()
+            },
+        ));
+    }
+    for effect in outcome.status_effects.iter() {
+        sections.push(TextSection::new(
+            format!("Starts the next wave with {effect}\n"),
+            TextStyle {
+                color: SWEET_VALUE_COLOR,
+                ..text_style.clone()
+            },
+        ));
+    }
+    if !outcome.infection_proof && outcome.infection_gain > 0.0 {
+        sections.push(TextSection::new(
+            format!("Infection {:+.2}\n", outcome.infection_gain),
+            TextStyle {
+                color: BITTER_VALUE_COLOR,
+                ..text_style.clone()
+            },
+        ));
+    }
+
+    let applied_to = if injection.targeted {
+        format!(
+            "Concentrated {:.1}x into the {} selected cells",
+            injection.dose,
+            injection.cell_ids.len()
+        )
+    } else {
+        format!(
+            "Applied to each of your {} cells",
+            player_resources.cell_army.len()
+        )
+    };
+    sections.push(TextSection::new(applied_to, text_style));
+
+    preview_text.sections = sections;
+}
+
+// An antidote about to be injected: the brew scaled to its dose and the cells it goes into
+#[derive(Debug, PartialEq)]
+struct Injection {
+    outcome: BrewOutcome,
+    dose: f32,
+    // the selected cells still in the army, or the whole army when none are selected
+    cell_ids: Vec<i32>,
+    targeted: bool,
+}
+
+// Shared by the Inject button and the preview, so the forecast is what the injection does
+fn plan_injection(
+    loaded: &[Substance],
+    cell_army: &BTreeMap<i32, CellBundle>,
+    injection_targets: &BTreeSet<i32>,
+) -> Injection {
+    let selected: Vec<i32> = injection_targets
+        .iter()
+        .copied()
+        .filter(|cell_id| cell_army.contains_key(cell_id))
+        .collect();
+    let dose = injection_dose(cell_army.len(), selected.len());
+    let targeted = !selected.is_empty();
+    let cell_ids = if targeted {
+        selected
+    } else {
+        cell_army.keys().copied().collect()
+    };
+
+    Injection {
+        outcome: brew(loaded).scaled(dose),
+        dose,
+        cell_ids,
+        targeted,
+    }
+}
+
+// The whole army's share of an antidote is split between the selected cells,
+// so fewer targets get a stronger dose each
+fn injection_dose(army_size: usize, targeted_cells: usize) -> f32 {
+    if targeted_cells == 0 {
+        return 1.0;
+    }
+
+    army_size as f32 / targeted_cells as f32
+}
+
+fn update_fusion_preview(
+    mut redraw_events: EventReader<RedrawSubstanceListEvent>,
+    mut bench_text_query: Query<&mut Text, With<FusionBenchText>>,
+    player_resources: Res<PlayerResource>,
+) {
+    if redraw_events.is_empty() {
+        return;
+    }
+    redraw_events.clear();
+
+    let Ok(mut bench_text) = bench_text_query.get_single_mut() else {
+        return;
+    };
+
+    bench_text.sections[0].value = if player_resources.fusion_bench.is_empty() {
+        "Right click substances to fuse them".to_string()
+    } else {
+        let bench_names: Vec<String> = player_resources
+            .fusion_bench
+            .values()
+            .map(|substance| format!("{} {:+.2}", substance.name, substance.value))
+            .collect();
+        let inputs: Vec<Substance> = player_resources.fusion_bench.values().cloned().collect();
+        match fuse(&inputs, 0) {
+            Ok(fused_substance) => format!(
+                "{} => {} {:+.2} ({})",
+                bench_names.join(" + "),
+                fused_substance.substance_type,
+                fused_substance.value,
+                fused_substance.rarity
+            ),
+            Err(_) => bench_names.join(" + "),
+        }
+    };
+}
+
 fn display_info_message(
     time: Res<Time>,
     mut commands: Commands,
@@ -1381,11 +2011,10 @@ fn display_info_message(
     if !info_place_holder_query.is_empty() {
         let mut to_display_message = "".to_string();
         let mut message_color = Color::RED;
-        for message_event in info_message_events.read() {
+        if let Some(message_event) = info_message_events.read().next() {
             let message = &message_event.0;
             to_display_message = message.clone();
             message_color = message_event.1;
-            break;
         }
 
         if display_timer.tick(time.delta()).finished() {
@@ -1440,3 +2069,106 @@ fn animate_test_tube_fill(
 
     player.play(animations.add(animation));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::npc::goodcell::GoodCellClass;
+
+    #[test]
+    fn the_whole_army_gets_a_normal_dose() {
+        assert_eq!(injection_dose(20, 0), 1.0);
+        assert_eq!(injection_dose(20, 20), 1.0);
+    }
+
+    #[test]
+    fn fewer_targets_get_a_stronger_dose() {
+        assert_eq!(injection_dose(20, 1), 20.0);
+        assert_eq!(injection_dose(20, 5), 4.0);
+    }
+
+    #[test]
+    fn a_concentrated_dose_does_not_add_more_infection() {
+        let sweet_attack = Substance {
+            substance_type: SubstanceType::Sweet,
+            target_attribute: TargetAttribute::Attack,
+            value: 2.0,
+            ..default()
+        };
+        let outcome = brew(&[sweet_attack]);
+        let targeted_outcome = outcome.scaled(injection_dose(20, 1));
+
+        assert_eq!(targeted_outcome.attack_gain, 40.0);
+        assert!(outcome.infection_gain > 0.0);
+        assert_eq!(targeted_outcome.infection_gain, outcome.infection_gain);
+    }
+
+    fn cell_army(size: i32) -> BTreeMap<i32, CellBundle> {
+        (0..size)
+            .map(|cell_id| {
+                (
+                    cell_id,
+                    CellBundle {
+                        cell_trans: Vec3::ZERO,
+                        cell_attribute: GoodCellClass::Naive.recruit_attribute(),
+                        stance: CellStance::default(),
+                        experience: CellExperience::default(),
+                        class: GoodCellClass::Naive,
+                        status_effects: StatusEffects::default(),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    fn loaded() -> Vec<Substance> {
+        vec![
+            Substance {
+                substance_type: SubstanceType::Sweet,
+                target_attribute: TargetAttribute::Health,
+                value: 3.0,
+                ..default()
+            },
+            Substance {
+                substance_type: SubstanceType::Bitter,
+                target_attribute: TargetAttribute::Attack,
+                value: -1.0,
+                ..default()
+            },
+        ]
+    }
+
+    #[test]
+    fn without_a_selection_the_whole_army_gets_the_brew() {
+        let injection = plan_injection(&loaded(), &cell_army(4), &BTreeSet::new());
+
+        assert!(!injection.targeted);
+        assert_eq!(injection.cell_ids, vec![0, 1, 2, 3]);
+        assert_eq!(injection.dose, 1.0);
+        assert_eq!(injection.outcome, brew(&loaded()));
+    }
+
+    #[test]
+    fn selected_cells_share_the_whole_dose() {
+        // cell 9 died since it was selected
+        let injection_targets = BTreeSet::from([1, 3, 9]);
+        let injection = plan_injection(&loaded(), &cell_army(4), &injection_targets);
+
+        assert!(injection.targeted);
+        assert_eq!(injection.cell_ids, vec![1, 3]);
+        assert_eq!(injection.dose, 2.0);
+        assert_eq!(injection.outcome, brew(&loaded()).scaled(2.0));
+        assert_eq!(injection.outcome.health_gain, 6.0);
+        assert_eq!(injection.outcome.new_cell_count, 1);
+    }
+
+    #[test]
+    fn only_dead_cells_selected_means_the_whole_army() {
+        let injection_targets = BTreeSet::from([7, 8]);
+        let injection = plan_injection(&loaded(), &cell_army(2), &injection_targets);
+
+        assert!(!injection.targeted);
+        assert_eq!(injection.cell_ids, vec![0, 1]);
+        assert_eq!(injection.outcome, brew(&loaded()));
+    }
+}
@@ -1,10 +1,14 @@
 use bevy::{app::AppExit, prelude::*};
 
 use crate::core;
+use crate::core::gamerng;
 use crate::core::states::GameState;
 use crate::core::userinterface::{GAME_THEME_COLOR, NORMAL_BUTTON};
 
 use super::playerresource::PlayerResource;
+use super::records;
+use super::savegame;
+use super::wavestats::RunStats;
 
 pub struct MenuPlugin;
 
@@ -17,6 +21,11 @@ impl Plugin for MenuPlugin {
                 OnExit(MenuState::Main),
                 core::despawn_entities::<OnMainMenuScreen>,
             )
+            .add_systems(OnEnter(MenuState::Records), setup_records_menu)
+            .add_systems(
+                OnExit(MenuState::Records),
+                core::despawn_entities::<OnRecordsMenuScreen>,
+            )
             .add_systems(Update, menu_action.run_if(in_state(GameState::Menu)));
     }
 }
@@ -24,19 +33,26 @@ impl Plugin for MenuPlugin {
 #[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Hash, States)]
 enum MenuState {
     Main,
+    Records,
     #[default]
     Disabled,
 }
 
 #[derive(Component)]
 enum MenuButtonAction {
+    Continue,
     Play,
+    Records,
+    BackToMainMenu,
     Quit,
 }
 
 #[derive(Component)]
 struct OnMainMenuScreen;
 
+#[derive(Component)]
+struct OnRecordsMenuScreen;
+
 fn setup_menu(mut menu_state: ResMut<NextState<MenuState>>) {
     menu_state.set(MenuState::Main);
 }
@@ -88,7 +104,7 @@ fn setup_main_menu(mut commands: Commands) {
                             "Antidotes",
                             TextStyle {
                                 font_size: 80.0,
-                                color: GAME_THEME_COLOR.into(),
+                                color: GAME_THEME_COLOR,
                                 ..default()
                             },
                         )
@@ -97,6 +113,23 @@ fn setup_main_menu(mut commands: Commands) {
                             ..default()
                         }),
                     );
+                    if savegame::has_saved_run() {
+                        parent
+                            .spawn((
+                                ButtonBundle {
+                                    style: button_style.clone(),
+                                    background_color: NORMAL_BUTTON.into(),
+                                    ..default()
+                                },
+                                MenuButtonAction::Continue,
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle::from_section(
+                                    "Continue",
+                                    button_txt_style.clone(),
+                                ));
+                            });
+                    }
                     parent
                         .spawn((
                             ButtonBundle {
@@ -112,6 +145,21 @@ fn setup_main_menu(mut commands: Commands) {
                                 button_txt_style.clone(),
                             ));
                         });
+                    parent
+                        .spawn((
+                            ButtonBundle {
+                                style: button_style.clone(),
+                                background_color: NORMAL_BUTTON.into(),
+                                ..default()
+                            },
+                            MenuButtonAction::Records,
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn(TextBundle::from_section(
+                                "Records",
+                                button_txt_style.clone(),
+                            ));
+                        });
                     parent
                         .spawn((
                             ButtonBundle {
@@ -128,6 +176,113 @@ fn setup_main_menu(mut commands: Commands) {
         });
 }
 
+fn setup_records_menu(mut commands: Commands) {
+    let button_style = Style {
+        width: Val::Px(250.0),
+        height: Val::Px(65.0),
+        margin: UiRect::all(Val::Px(20.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+
+    let button_txt_style = TextStyle {
+        font_size: 40.0,
+        color: Color::rgb(0.9, 0.9, 0.9),
+        ..default()
+    };
+
+    let record_txt_style = TextStyle {
+        font_size: 24.0,
+        color: GAME_THEME_COLOR,
+        ..default()
+    };
+
+    let runs = records::load_records();
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            OnRecordsMenuScreen,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    background_color: Color::AQUAMARINE.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(
+                        TextBundle::from_section(
+                            "Records",
+                            TextStyle {
+                                font_size: 60.0,
+                                color: GAME_THEME_COLOR,
+                                ..default()
+                            },
+                        )
+                        .with_style(Style {
+                            margin: UiRect::all(Val::Px(30.0)),
+                            ..default()
+                        }),
+                    );
+                    if runs.is_empty() {
+                        parent.spawn(TextBundle::from_section(
+                            "No runs finished yet",
+                            record_txt_style.clone(),
+                        ));
+                    }
+                    for (rank, run) in runs.iter().enumerate() {
+                        parent.spawn(
+                            TextBundle::from_section(
+                                format!(
+                                    "{}. Wave {}  -  Army {}  -  Brewed {}  -  Seed {}  -  {}",
+                                    rank + 1,
+                                    run.waves_survived,
+                                    run.peak_army_size,
+                                    run.substances_brewed,
+                                    run.seed,
+                                    run.date()
+                                ),
+                                record_txt_style.clone(),
+                            )
+                            .with_style(Style {
+                                margin: UiRect::horizontal(Val::Px(20.0)),
+                                ..default()
+                            }),
+                        );
+                    }
+                    parent
+                        .spawn((
+                            ButtonBundle {
+                                style: button_style,
+                                background_color: NORMAL_BUTTON.into(),
+                                ..default()
+                            },
+                            MenuButtonAction::BackToMainMenu,
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn(TextBundle::from_section("Back", button_txt_style));
+                        });
+                });
+        });
+}
+
+#[allow(clippy::type_complexity)]
 fn menu_action(
     interaction_query: Query<
         (&Interaction, &MenuButtonAction),
@@ -136,15 +291,31 @@ fn menu_action(
     mut app_exit_events: EventWriter<AppExit>,
     mut menu_state: ResMut<NextState<MenuState>>,
     mut game_state: ResMut<NextState<GameState>>,
+    mut player_resources: ResMut<PlayerResource>,
 ) {
     for (interaction, menu_button_action) in &interaction_query {
         if *interaction == Interaction::Pressed {
             match menu_button_action {
                 MenuButtonAction::Quit => app_exit_events.send(AppExit),
+                MenuButtonAction::Continue => {
+                    if let Some(saved_player_resources) = savegame::load_saved_run() {
+                        *player_resources = saved_player_resources;
+                        game_state.set(GameState::Prepare);
+                        menu_state.set(MenuState::Disabled);
+                    }
+                }
                 MenuButtonAction::Play => {
+                    // the new run is only saved once it clears a wave, the old save would
+                    // be continued until then
+                    if let Some(abandoned_run) = savegame::load_saved_run() {
+                        records::record_run(&abandoned_run);
+                    }
+                    savegame::delete_saved_run();
                     game_state.set(GameState::Prepare);
                     menu_state.set(MenuState::Disabled);
                 }
+                MenuButtonAction::Records => menu_state.set(MenuState::Records),
+                MenuButtonAction::BackToMainMenu => menu_state.set(MenuState::Main),
             }
         }
     }
@@ -155,6 +326,9 @@ fn reset_player_resources(mut player_resources: ResMut<PlayerResource>) {
     player_resources.good_cell_id.0 = 0;
     player_resources.loaded_substances.clear();
     player_resources.substance_collection.clear();
+    player_resources.fusion_bench.clear();
     player_resources.wave_num = 0;
     player_resources.substance_id_gen.0 = 0;
+    player_resources.seed = gamerng::new_run_seed();
+    player_resources.run_stats = RunStats::default();
 }
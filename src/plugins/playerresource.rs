@@ -1,10 +1,12 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
+use crate::core::gamerng;
 use crate::npc::{goodcell::GoodCellId, cell::CellBundle};
 
-use super::antidote::Substance;
+use super::{antidote::Substance, wavestats::RunStats};
 
 pub struct PlayerResourcePlugin;
 
@@ -14,33 +16,44 @@ impl Plugin for PlayerResourcePlugin {
     }
 }
 
-#[derive(Resource)]
+#[derive(Resource, Serialize, Deserialize)]
 pub struct PlayerResource {
-    pub cell_army: HashMap<i32, CellBundle>,
-    pub substance_collection: HashMap<i32, Substance>,
-    pub loaded_substances: HashMap<i32, Substance>,
+    pub cell_army: BTreeMap<i32, CellBundle>,
+    pub substance_collection: BTreeMap<i32, Substance>,
+    pub loaded_substances: BTreeMap<i32, Substance>,
+    #[serde(default)]
+    pub fusion_bench: BTreeMap<i32, Substance>,
     pub good_cell_id: GoodCellId,
     pub wave_num: i32,
-    pub substance_id_gen: SubstanceIdGen
+    pub substance_id_gen: SubstanceIdGen,
+    // saves from before the seed existed continue with a new one
+    #[serde(default = "gamerng::new_run_seed")]
+    pub seed: u64,
+    #[serde(default)]
+    pub run_stats: RunStats,
 }
 
-#[derive(Resource)]
+#[derive(Resource, Serialize, Deserialize)]
 pub struct SubstanceIdGen(pub i32);
 
 fn setup_player_resources(mut commands: Commands) {
-    let cell_army: HashMap<i32, CellBundle> = HashMap::new();
-    let substance_collection: HashMap<i32, Substance> = HashMap::new();
-    let loaded_substances: HashMap<i32, Substance> = HashMap::new();
+    let cell_army: BTreeMap<i32, CellBundle> = BTreeMap::new();
+    let substance_collection: BTreeMap<i32, Substance> = BTreeMap::new();
+    let loaded_substances: BTreeMap<i32, Substance> = BTreeMap::new();
     let good_cell_id = GoodCellId(0);
     let wave_num = 0;
     let substance_id_gen = SubstanceIdGen(0);
+    let seed = gamerng::new_run_seed();
 
     commands.insert_resource(PlayerResource {
         cell_army,
         substance_collection,
         loaded_substances,
+        fusion_bench: BTreeMap::new(),
         good_cell_id,
         wave_num,
-        substance_id_gen
+        substance_id_gen,
+        seed,
+        run_stats: RunStats::default(),
     });
 }
@@ -0,0 +1,203 @@
+// Leaderboard of finished runs, kept in the same storage as the save game
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::core::{states::GameState, storage};
+
+use super::playerresource::PlayerResource;
+
+// Bump this whenever the layout of RunRecord changes, older records are then dropped
+const RECORDS_VERSION: u32 = 1;
+const RECORDS_KEY: &str = "records";
+const MAX_RECORDS: usize = 20;
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+pub struct RecordsPlugin;
+
+impl Plugin for RecordsPlugin {
+    fn build(&self, app: &mut App) {
+        // a run left through the menu is still saved and can be continued,
+        // it is recorded when a new run replaces it in the menu
+        app.add_systems(OnEnter(GameState::GameOver), record_finished_run);
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub waves_survived: i32,
+    pub peak_army_size: usize,
+    pub substances_brewed: i32,
+    pub seed: u64,
+    // seconds since the unix epoch
+    pub finished_at: u64,
+}
+
+impl RunRecord {
+    // The day the run ended as YYYY-MM-DD, in UTC
+    pub fn date(&self) -> String {
+        // days to civil date, see http://howardhinnant.github.io/date_algorithms.html
+        let days = (self.finished_at / SECONDS_PER_DAY) as i64 + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+        let month = if month_index < 10 {
+            month_index + 3
+        } else {
+            month_index - 9
+        };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+        format!("{year:04}-{month:02}-{day:02}")
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct RunRecords {
+    version: u32,
+    runs: Vec<RunRecord>,
+}
+
+// Best runs first
+pub fn load_records() -> Vec<RunRecord> {
+    let Some(contents) = storage::read(RECORDS_KEY) else {
+        return vec![];
+    };
+    let records = match ron::from_str::<RunRecords>(&contents) {
+        Ok(records) => records,
+        Err(err) => {
+            warn!("Ignoring unreadable records: {err}");
+            return vec![];
+        }
+    };
+
+    if records.version != RECORDS_VERSION {
+        warn!(
+            "Ignoring records version {}, expected {RECORDS_VERSION}",
+            records.version
+        );
+        return vec![];
+    }
+
+    let mut runs = records.runs;
+    sort_records(&mut runs);
+    runs
+}
+
+fn sort_records(runs: &mut [RunRecord]) {
+    runs.sort_by(|a, b| {
+        b.waves_survived
+            .cmp(&a.waves_survived)
+            .then(b.finished_at.cmp(&a.finished_at))
+    });
+}
+
+fn record_finished_run(player_resources: Res<PlayerResource>) {
+    record_run(&player_resources);
+}
+
+pub fn record_run(player_resource: &PlayerResource) {
+    let mut runs = load_records();
+    runs.push(RunRecord {
+        waves_survived: player_resource.wave_num,
+        peak_army_size: player_resource.run_stats.peak_army_size,
+        substances_brewed: player_resource.run_stats.substances_brewed,
+        seed: player_resource.seed,
+        finished_at: unix_time_now(),
+    });
+    sort_records(&mut runs);
+    runs.truncate(MAX_RECORDS);
+
+    let records = RunRecords {
+        version: RECORDS_VERSION,
+        runs,
+    };
+    let contents = match ron::ser::to_string_pretty(&records, default()) {
+        Ok(contents) => contents,
+        Err(err) => {
+            error!("Could not serialize the records: {err}");
+            return;
+        }
+    };
+
+    if let Err(err) = storage::write(RECORDS_KEY, &contents) {
+        error!("Could not save the records: {err}");
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn unix_time_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+// SystemTime is not available in the browser
+#[cfg(target_arch = "wasm32")]
+fn unix_time_now() -> u64 {
+    (js_sys::Date::now() / 1000.0) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finished_at(finished_at: u64) -> RunRecord {
+        RunRecord {
+            waves_survived: 0,
+            peak_army_size: 0,
+            substances_brewed: 0,
+            seed: 0,
+            finished_at,
+        }
+    }
+
+    #[test]
+    fn the_epoch_is_the_first_of_january_1970() {
+        assert_eq!(finished_at(0).date(), "1970-01-01");
+        assert_eq!(finished_at(SECONDS_PER_DAY - 1).date(), "1970-01-01");
+    }
+
+    #[test]
+    fn leap_days_are_counted() {
+        assert_eq!(finished_at(1_709_164_800).date(), "2024-02-29");
+        assert_eq!(
+            finished_at(1_709_164_800 + SECONDS_PER_DAY).date(),
+            "2024-03-01"
+        );
+        assert_eq!(finished_at(951_782_400).date(), "2000-02-29");
+        // 2100 is not a leap year
+        assert_eq!(finished_at(4_107_542_400).date(), "2100-03-01");
+    }
+
+    #[test]
+    fn the_year_changes_at_midnight() {
+        assert_eq!(finished_at(1_704_067_199).date(), "2023-12-31");
+        assert_eq!(finished_at(1_704_067_200).date(), "2024-01-01");
+    }
+
+    #[test]
+    fn the_most_waves_come_first_then_the_latest() {
+        let mut runs = vec![
+            RunRecord {
+                waves_survived: 2,
+                ..finished_at(30)
+            },
+            RunRecord {
+                waves_survived: 5,
+                ..finished_at(10)
+            },
+            RunRecord {
+                waves_survived: 2,
+                ..finished_at(40)
+            },
+        ];
+        sort_records(&mut runs);
+        let order: Vec<u64> = runs.iter().map(|run| run.finished_at).collect();
+        assert_eq!(order, vec![10, 40, 30]);
+    }
+}
@@ -0,0 +1,145 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::core::{states::GameState, storage};
+
+use super::playerresource::PlayerResource;
+
+// Bump this whenever the layout of PlayerResource changes
+const SAVE_VERSION: u32 = 2;
+// Oldest save that still loads, the fields added since then fall back to their defaults.
+// Raise it when a change can not be defaulted, older saves are then ignored
+const MIN_SAVE_VERSION: u32 = 1;
+const SAVE_KEY: &str = "savegame";
+
+pub struct SaveGamePlugin;
+
+impl Plugin for SaveGamePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnExit(GameState::Prepare), save_run)
+            .add_systems(OnExit(GameState::GameFinish), save_run)
+            .add_systems(OnEnter(GameState::GameOver), delete_saved_run);
+    }
+}
+
+#[derive(Serialize)]
+struct SaveGameRef<'a> {
+    version: u32,
+    player_resource: &'a PlayerResource,
+}
+
+#[derive(Deserialize)]
+struct SaveGame {
+    version: u32,
+    player_resource: PlayerResource,
+}
+
+// Only checks that there is a save, it is read once the player continues
+pub fn has_saved_run() -> bool {
+    storage::exists(SAVE_KEY)
+}
+
+pub fn load_saved_run() -> Option<PlayerResource> {
+    parse_saved_run(&storage::read(SAVE_KEY)?)
+}
+
+fn parse_saved_run(contents: &str) -> Option<PlayerResource> {
+    let save_game = match ron::from_str::<SaveGame>(contents) {
+        Ok(save_game) => save_game,
+        Err(err) => {
+            warn!("Ignoring unreadable save file: {err}");
+            return None;
+        }
+    };
+
+    if !(MIN_SAVE_VERSION..=SAVE_VERSION).contains(&save_game.version) {
+        warn!(
+            "Ignoring save file version {}, expected {MIN_SAVE_VERSION} to {SAVE_VERSION}",
+            save_game.version
+        );
+        return None;
+    }
+
+    Some(save_game.player_resource)
+}
+
+fn save_run(player_resources: Res<PlayerResource>) {
+    // nothing worth resuming before the first wave is cleared
+    if player_resources.wave_num == 0 {
+        return;
+    }
+
+    let save_game = SaveGameRef {
+        version: SAVE_VERSION,
+        player_resource: &player_resources,
+    };
+    let contents = match ron::ser::to_string_pretty(&save_game, default()) {
+        Ok(contents) => contents,
+        Err(err) => {
+            error!("Could not serialize the run: {err}");
+            return;
+        }
+    };
+
+    if let Err(err) = storage::write(SAVE_KEY, &contents) {
+        error!("Could not save the run: {err}");
+    }
+}
+
+pub fn delete_saved_run() {
+    if let Err(err) = storage::remove(SAVE_KEY) {
+        error!("Could not delete the saved run: {err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::antidote::SubstanceRarity;
+
+    // a version 1 save, written before runs had a seed
+    const VERSION_1_SAVE: &str = r#"(
+        version: 1,
+        player_resource: (
+            cell_army: {},
+            substance_collection: {
+                3: (
+                    id: 3,
+                    name: "Atagen",
+                    target_attribute: Attack,
+                    value: 4.5,
+                    substance_type: Sweet,
+                    icon: "sprites/sub_attack.png",
+                    rarity: Common,
+                ),
+            },
+            loaded_substances: {},
+            good_cell_id: (6),
+            wave_num: 2,
+            substance_id_gen: (4),
+        ),
+    )"#;
+
+    #[test]
+    fn older_saves_load_with_defaults() {
+        let player_resource = parse_saved_run(VERSION_1_SAVE).unwrap();
+        assert_eq!(player_resource.wave_num, 2);
+        assert_eq!(player_resource.substance_collection[&3].value, 4.5);
+    }
+
+    #[test]
+    fn substances_without_a_rarity_load_as_common() {
+        let save = VERSION_1_SAVE.replace("rarity: Common,", "");
+        let player_resource = parse_saved_run(&save).unwrap();
+        assert_eq!(
+            player_resource.substance_collection[&3].rarity,
+            SubstanceRarity::Common
+        );
+    }
+
+    #[test]
+    fn newer_saves_are_ignored() {
+        let newer = VERSION_1_SAVE.replace("version: 1", &format!("version: {}", SAVE_VERSION + 1));
+        assert!(parse_saved_run(&newer).is_none());
+    }
+}
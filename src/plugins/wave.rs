@@ -0,0 +1,201 @@
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::npc::badcell::BadCellArchetype;
+
+const WAVE_TABLE_PATH: &str = "data/base.waves.ron";
+
+// Used for every wave past the end of the wave table
+const BAD_CELL_BASE_COUNT: i32 = 25;
+const BAD_CELL_COUNT_PER_WAVE: i32 = 3;
+const BAD_CELL_BASE_STRENGTH_MULTIPLIER: f32 = 0.25;
+const BAD_CELL_SPAWN_RING_INNER: f32 = 300.0;
+const BAD_CELL_SPAWN_RING_OUTER: f32 = 320.0;
+const BAD_CELL_SEARCH_RADIUS: f32 = 1000.0;
+const BAD_CELL_ATTACK_RANGE: f32 = 10.0;
+
+pub struct WavePlugin;
+
+impl Plugin for WavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<WaveTable>()
+            .init_asset_loader::<WaveTableLoader>()
+            .insert_resource(WaveTableResource(WaveTable::default()))
+            .add_systems(Startup, setup_wave_table)
+            .add_systems(Update, sync_wave_table_resource);
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+pub struct WaveDefinition {
+    pub enemy_count: i32,
+    pub strength: f32,
+    pub spawn_ring_inner: f32,
+    pub spawn_ring_outer: f32,
+    pub search_radius: f32,
+    pub attack_range: f32,
+    // archetypes to spawn, with their relative weights
+    pub composition: Vec<(BadCellArchetype, f32)>,
+}
+
+impl WaveDefinition {
+    pub fn from_formula(wave_num: i32, composition: Vec<(BadCellArchetype, f32)>) -> Self {
+        // enemies only get stronger every third wave
+        let strength = if wave_num != 0 && wave_num % 3 == 0 {
+            wave_num as f32 * BAD_CELL_BASE_STRENGTH_MULTIPLIER
+        } else {
+            1.0
+        };
+
+        Self {
+            enemy_count: BAD_CELL_BASE_COUNT + wave_num * BAD_CELL_COUNT_PER_WAVE,
+            strength,
+            spawn_ring_inner: BAD_CELL_SPAWN_RING_INNER,
+            spawn_ring_outer: BAD_CELL_SPAWN_RING_OUTER,
+            search_radius: BAD_CELL_SEARCH_RADIUS,
+            attack_range: BAD_CELL_ATTACK_RANGE,
+            composition,
+        }
+    }
+}
+
+#[derive(Asset, TypePath, Clone, Default, Deserialize)]
+pub struct WaveTable {
+    pub waves: Vec<WaveDefinition>,
+}
+
+impl WaveTable {
+    pub fn wave(&self, wave_num: i32) -> WaveDefinition {
+        if let Some(wave) = usize::try_from(wave_num)
+            .ok()
+            .and_then(|wave_idx| self.waves.get(wave_idx))
+        {
+            return wave.clone();
+        }
+
+        // keep the enemy mix of the last designed wave
+        let composition = self
+            .waves
+            .last()
+            .map(|last_wave| last_wave.composition.clone())
+            .unwrap_or_else(|| vec![(BadCellArchetype::default(), 1.0)]);
+        WaveDefinition::from_formula(wave_num, composition)
+    }
+}
+
+#[derive(Default)]
+pub struct WaveTableLoader;
+
+#[derive(Debug, Error)]
+pub enum WaveTableLoaderError {
+    #[error("Could not read the wave table: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse the wave table: {0}")]
+    RonSpanned(#[from] ron::error::SpannedError),
+}
+
+impl AssetLoader for WaveTableLoader {
+    type Asset = WaveTable;
+    type Settings = ();
+    type Error = WaveTableLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let wave_table = ron::de::from_bytes::<WaveTable>(&bytes)?;
+            Ok(wave_table)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["waves.ron"]
+    }
+}
+
+// Until the wave table is loaded every wave falls back to the formula
+#[derive(Resource)]
+pub struct WaveTableResource(pub WaveTable);
+
+#[derive(Resource)]
+struct WaveTableHandle(Handle<WaveTable>);
+
+fn setup_wave_table(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(WaveTableHandle(asset_server.load(WAVE_TABLE_PATH)));
+}
+
+fn sync_wave_table_resource(
+    mut wave_table_events: EventReader<AssetEvent<WaveTable>>,
+    wave_tables: Res<Assets<WaveTable>>,
+    wave_table_handle: Res<WaveTableHandle>,
+    mut wave_table_resource: ResMut<WaveTableResource>,
+) {
+    for wave_table_event in wave_table_events.read() {
+        if !wave_table_event.is_loaded_with_dependencies(&wave_table_handle.0)
+            && !wave_table_event.is_modified(&wave_table_handle.0)
+        {
+            continue;
+        }
+
+        if let Some(wave_table) = wave_tables.get(&wave_table_handle.0) {
+            wave_table_resource.0 = wave_table.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base_wave_table() -> WaveTable {
+        ron::from_str(include_str!("../../assets/data/base.waves.ron")).unwrap()
+    }
+
+    #[test]
+    fn the_base_table_follows_the_formula() {
+        let wave_table = base_wave_table();
+        assert!(!wave_table.waves.is_empty());
+        for (wave_num, wave) in wave_table.waves.iter().enumerate() {
+            let formula = WaveDefinition::from_formula(wave_num as i32, wave.composition.clone());
+            assert_eq!(*wave, formula, "wave {wave_num}");
+        }
+        assert_eq!(wave_table.wave(3).strength, 0.75);
+    }
+
+    #[test]
+    fn waves_past_the_table_use_the_formula_with_the_last_mix() {
+        let wave_table = base_wave_table();
+        let last_composition = wave_table.waves.last().unwrap().composition.clone();
+        for wave_num in [wave_table.waves.len() as i32, 9, 20] {
+            assert_eq!(
+                wave_table.wave(wave_num),
+                WaveDefinition::from_formula(wave_num, last_composition.clone())
+            );
+        }
+        assert_eq!(
+            wave_table.wave(9).strength,
+            9.0 * BAD_CELL_BASE_STRENGTH_MULTIPLIER
+        );
+        assert_eq!(wave_table.wave(10).strength, 1.0);
+    }
+
+    #[test]
+    fn an_empty_table_spawns_the_default_archetype() {
+        let wave = WaveTable::default().wave(2);
+        assert_eq!(
+            wave.enemy_count,
+            BAD_CELL_BASE_COUNT + 2 * BAD_CELL_COUNT_PER_WAVE
+        );
+        assert_eq!(wave.composition, vec![(BadCellArchetype::default(), 1.0)]);
+    }
+}
@@ -0,0 +1,379 @@
+// Statistics of the current wave, built from the combat events, plus run totals
+// that are kept in the player resources
+use std::{collections::BTreeMap, time::Duration};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    core::{simulation::WaveResult, states::GameState},
+    npc::{
+        badcell,
+        cell::{self, CellConversion},
+        goodcell::GoodCell,
+    },
+};
+
+use super::{
+    combatlog::{
+        CellConverted, CellDamaged, CellInfected, CellKilled, CellRecovered, SubstanceDropped,
+        WaveEnded,
+    },
+    playerresource::PlayerResource,
+};
+
+// Number of cells listed on the report screens
+const TOP_CELL_COUNT: usize = 5;
+
+pub struct WaveStatsPlugin;
+
+impl Plugin for WaveStatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WaveStats>()
+            .add_systems(OnEnter(GameState::Game), reset_wave_stats)
+            // damage is attributed before cells get converted or despawned
+            .add_systems(
+                FixedUpdate,
+                track_cell_stats
+                    .after(badcell::move_attack)
+                    .before(cell::track_cell_infection)
+                    .run_if(in_state(GameState::Game)),
+            )
+            .add_systems(PostUpdate, track_wave_stats);
+    }
+}
+
+#[derive(Default, Clone, Debug)]
+pub struct CellWaveStats {
+    pub damage_dealt: f32,
+    pub damage_taken: f32,
+}
+
+#[derive(Resource, Default, Clone, Debug)]
+pub struct WaveStats {
+    pub wave_num: i32,
+    pub result: Option<WaveResult>,
+    pub enemies_killed: i32,
+    pub cells_lost: i32,
+    pub cells_cured: i32,
+    pub infections: i32,
+    pub golden_recoveries: i32,
+    pub damage_dealt: f32,
+    pub damage_taken: f32,
+    // keyed by good cell id
+    pub cells: BTreeMap<i32, CellWaveStats>,
+    // keyed by substance name
+    pub substances_collected: BTreeMap<String, i32>,
+    // from the wave start to the last enemy killed, or to the defeat
+    pub duration: Duration,
+    started_at: Duration,
+}
+
+impl WaveStats {
+    // The cells that dealt the most damage, best first
+    pub fn top_cells(&self) -> Vec<(i32, &CellWaveStats)> {
+        let mut top_cells: Vec<_> = self
+            .cells
+            .iter()
+            .map(|(cell_id, cell_stats)| (*cell_id, cell_stats))
+            .collect();
+        top_cells.sort_by(|(_, a), (_, b)| b.damage_dealt.total_cmp(&a.damage_dealt));
+        top_cells.truncate(TOP_CELL_COUNT);
+        top_cells
+    }
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RunStats {
+    pub waves_cleared: i32,
+    pub peak_army_size: usize,
+    pub substances_brewed: i32,
+    pub enemies_killed: i32,
+    pub cells_lost: i32,
+    pub cells_cured: i32,
+    pub infections: i32,
+    pub golden_recoveries: i32,
+    pub damage_dealt: f32,
+    pub damage_taken: f32,
+    pub substances_collected: i32,
+    pub time_played: f32,
+}
+
+impl RunStats {
+    pub fn note_army_size(&mut self, army_size: usize) {
+        self.peak_army_size = self.peak_army_size.max(army_size);
+    }
+
+    fn add_wave(&mut self, wave_stats: &WaveStats) {
+        if wave_stats.result == Some(WaveResult::Victory) {
+            self.waves_cleared += 1;
+        }
+        self.enemies_killed += wave_stats.enemies_killed;
+        self.cells_lost += wave_stats.cells_lost;
+        self.cells_cured += wave_stats.cells_cured;
+        self.infections += wave_stats.infections;
+        self.golden_recoveries += wave_stats.golden_recoveries;
+        self.damage_dealt += wave_stats.damage_dealt;
+        self.damage_taken += wave_stats.damage_taken;
+        self.substances_collected += wave_stats.substances_collected.values().sum::<i32>();
+        self.time_played += wave_stats.duration.as_secs_f32();
+    }
+}
+
+fn reset_wave_stats(
+    time: Res<Time>,
+    player_resources: Res<PlayerResource>,
+    mut wave_stats: ResMut<WaveStats>,
+) {
+    *wave_stats = WaveStats {
+        wave_num: player_resources.wave_num,
+        started_at: time.elapsed(),
+        ..default()
+    };
+}
+
+fn track_cell_stats(
+    mut wave_stats: ResMut<WaveStats>,
+    mut damaged_events: EventReader<CellDamaged>,
+    goodcell_query: Query<&GoodCell>,
+) {
+    for damaged in damaged_events.read() {
+        if let Ok(good_cell) = goodcell_query.get(damaged.attacker) {
+            wave_stats.damage_dealt += damaged.amount;
+            wave_stats
+                .cells
+                .entry(good_cell.cell_id)
+                .or_default()
+                .damage_dealt += damaged.amount;
+        }
+        if let Ok(good_cell) = goodcell_query.get(damaged.entity) {
+            wave_stats.damage_taken += damaged.amount;
+            wave_stats
+                .cells
+                .entry(good_cell.cell_id)
+                .or_default()
+                .damage_taken += damaged.amount;
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn track_wave_stats(
+    time: Res<Time>,
+    mut wave_stats: ResMut<WaveStats>,
+    mut player_resources: ResMut<PlayerResource>,
+    mut infected_events: EventReader<CellInfected>,
+    mut recovered_events: EventReader<CellRecovered>,
+    mut killed_events: EventReader<CellKilled>,
+    mut converted_events: EventReader<CellConverted>,
+    mut dropped_events: EventReader<SubstanceDropped>,
+    mut wave_ended_events: EventReader<WaveEnded>,
+) {
+    let elapsed = time.elapsed().saturating_sub(wave_stats.started_at);
+
+    wave_stats.infections += infected_events.read().count() as i32;
+    wave_stats.golden_recoveries += recovered_events.read().count() as i32;
+
+    for killed in killed_events.read() {
+        if killed.good_cell_id.is_some() {
+            wave_stats.cells_lost += 1;
+        } else {
+            wave_stats.enemies_killed += 1;
+            wave_stats.duration = elapsed;
+        }
+    }
+
+    for converted in converted_events.read() {
+        match converted.conversion {
+            CellConversion::Infected => wave_stats.cells_lost += 1,
+            CellConversion::Cured => {
+                // cured cells join the army in the middle of the wave
+                wave_stats.cells_cured += 1;
+                let army_size = player_resources.cell_army.len();
+                player_resources.run_stats.note_army_size(army_size);
+            }
+        }
+    }
+
+    for dropped in dropped_events.read() {
+        *wave_stats
+            .substances_collected
+            .entry(dropped.name.clone())
+            .or_default() += 1;
+    }
+
+    for wave_ended in wave_ended_events.read() {
+        wave_stats.result = Some(wave_ended.result);
+        if wave_ended.result != WaveResult::Victory {
+            wave_stats.duration = elapsed;
+        }
+        player_resources.run_stats.add_wave(&wave_stats);
+    }
+}
+
+fn stats_text(value: String) -> TextBundle {
+    TextBundle::from_section(
+        value,
+        TextStyle {
+            font_size: 24.0,
+            color: Color::rgb(0.9, 0.9, 0.9),
+            ..default()
+        },
+    )
+    .with_style(Style {
+        margin: UiRect::all(Val::Px(3.0)),
+        ..default()
+    })
+}
+
+// The wave report shown on the GameFinish and GameOver screens
+pub fn spawn_wave_stats(parent: &mut ChildBuilder, wave_stats: &WaveStats) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                margin: UiRect::all(Val::Px(10.0)),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn(stats_text(format!(
+                "Wave {} in {:.1}s",
+                wave_stats.wave_num,
+                wave_stats.duration.as_secs_f32()
+            )));
+            parent.spawn(stats_text(format!(
+                "Enemies killed: {}   Cells lost: {}   Cells cured: {}",
+                wave_stats.enemies_killed, wave_stats.cells_lost, wave_stats.cells_cured
+            )));
+            parent.spawn(stats_text(format!(
+                "Damage dealt: {:.0}   Damage taken: {:.0}",
+                wave_stats.damage_dealt, wave_stats.damage_taken
+            )));
+            parent.spawn(stats_text(format!(
+                "Infections: {}   Golden recoveries: {}",
+                wave_stats.infections, wave_stats.golden_recoveries
+            )));
+
+            let substances_collected = if wave_stats.substances_collected.is_empty() {
+                "none".to_string()
+            } else {
+                wave_stats
+                    .substances_collected
+                    .iter()
+                    .map(|(name, count)| format!("{name} x{count}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            parent.spawn(stats_text(format!(
+                "Substances collected: {substances_collected}"
+            )));
+
+            for (cell_id, cell_stats) in wave_stats.top_cells() {
+                parent.spawn(stats_text(format!(
+                    "Cell #{cell_id}: dealt {:.0}, taken {:.0}",
+                    cell_stats.damage_dealt, cell_stats.damage_taken
+                )));
+            }
+        });
+}
+
+pub fn spawn_run_stats(parent: &mut ChildBuilder, run_stats: &RunStats) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                margin: UiRect::all(Val::Px(10.0)),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn(stats_text(format!(
+                "Run: {} waves cleared in {:.0}s",
+                run_stats.waves_cleared, run_stats.time_played
+            )));
+            parent.spawn(stats_text(format!(
+                "Enemies killed: {}   Cells lost: {}   Cells cured: {}",
+                run_stats.enemies_killed, run_stats.cells_lost, run_stats.cells_cured
+            )));
+            parent.spawn(stats_text(format!(
+                "Damage dealt: {:.0}   Damage taken: {:.0}   Substances: {}",
+                run_stats.damage_dealt, run_stats.damage_taken, run_stats.substances_collected
+            )));
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cell_stats(damage_dealt: f32) -> CellWaveStats {
+        CellWaveStats {
+            damage_dealt,
+            damage_taken: 0.0,
+        }
+    }
+
+    #[test]
+    fn top_cells_are_sorted_by_damage_dealt() {
+        let wave_stats = WaveStats {
+            cells: (0..TOP_CELL_COUNT as i32 + 2)
+                .map(|cell_id| (cell_id, cell_stats(cell_id as f32 * 10.0)))
+                .collect(),
+            ..default()
+        };
+
+        let top_cells = wave_stats.top_cells();
+        assert_eq!(top_cells.len(), TOP_CELL_COUNT);
+        assert_eq!(top_cells[0].0, TOP_CELL_COUNT as i32 + 1);
+        assert!(top_cells
+            .windows(2)
+            .all(|pair| pair[0].1.damage_dealt >= pair[1].1.damage_dealt));
+    }
+
+    #[test]
+    fn run_stats_add_up_the_waves() {
+        let mut run_stats = RunStats::default();
+        let cleared = WaveStats {
+            result: Some(WaveResult::Victory),
+            enemies_killed: 12,
+            damage_dealt: 300.0,
+            substances_collected: BTreeMap::from([
+                ("Atagen".to_string(), 2),
+                ("Helagen".to_string(), 1),
+            ]),
+            duration: Duration::from_secs(30),
+            ..default()
+        };
+        let lost = WaveStats {
+            result: Some(WaveResult::Defeat),
+            enemies_killed: 3,
+            cells_lost: 6,
+            duration: Duration::from_secs(10),
+            ..default()
+        };
+
+        run_stats.add_wave(&cleared);
+        run_stats.add_wave(&lost);
+
+        assert_eq!(run_stats.waves_cleared, 1);
+        assert_eq!(run_stats.enemies_killed, 15);
+        assert_eq!(run_stats.cells_lost, 6);
+        assert_eq!(run_stats.damage_dealt, 300.0);
+        assert_eq!(run_stats.substances_collected, 3);
+        assert_eq!(run_stats.time_played, 40.0);
+    }
+
+    #[test]
+    fn the_peak_army_size_is_kept() {
+        let mut run_stats = RunStats::default();
+        run_stats.note_army_size(8);
+        run_stats.note_army_size(5);
+        assert_eq!(run_stats.peak_army_size, 8);
+    }
+}
@@ -0,0 +1,117 @@
+// Runs whole waves through the headless simulation
+use std::{collections::BTreeMap, time::Duration};
+
+use antidotes::{
+    core::simulation::{simulate_wave, SimulationConfig, WaveReport},
+    npc::{
+        cell::{CellAttack, CellAttribute, CellBundle},
+        experience::CellExperience,
+        goodcell::{CellStance, GoodCellClass},
+        statuseffect::StatusEffects,
+    },
+    plugins::{
+        antidote::parse_substance_catalog,
+        combatlog::{CombatEvent, CombatLog},
+    },
+};
+use bevy::prelude::*;
+
+fn cell_army() -> BTreeMap<i32, CellBundle> {
+    (0..6)
+        .map(|id| {
+            (
+                id,
+                CellBundle {
+                    cell_trans: Vec3::new(id as f32 * 40.0 - 100.0, 10.0, 0.0),
+                    cell_attribute: CellAttribute {
+                        health: 50.0,
+                        immune: 30.0,
+                        infection: 0.0,
+                        cure: 0.0,
+                        infection_proof: false,
+                        cell_attack: CellAttack::new(0.5, 20.0),
+                    },
+                    stance: CellStance::GuardCentre,
+                    experience: CellExperience::default(),
+                    class: GoodCellClass::Naive,
+                    status_effects: StatusEffects::default(),
+                },
+            )
+        })
+        .collect()
+}
+
+fn base_config(seed: u64, wave_num: i32) -> SimulationConfig {
+    SimulationConfig {
+        seed,
+        wave_num,
+        cell_army: cell_army(),
+        substances: parse_substance_catalog(include_bytes!("../assets/data/base.substances.ron"))
+            .unwrap()
+            .substances,
+        wave_table: ron::from_str(include_str!("../assets/data/base.waves.ron")).unwrap(),
+        ..default()
+    }
+}
+
+// Everything a run reports, in a form two runs can be compared by
+fn summary(report: &WaveReport) -> String {
+    format!(
+        "{:?} {:?} enemies={} drops={}\n{}\n{}",
+        report.result,
+        report.elapsed,
+        report.surviving_enemies,
+        report.substances_dropped,
+        ron::to_string(&report.surviving_cells).unwrap(),
+        ron::to_string(&report.combat_log).unwrap(),
+    )
+}
+
+// Number of hits and the damage they dealt
+fn damage_dealt(combat_log: &CombatLog) -> (usize, f32) {
+    combat_log
+        .entries
+        .iter()
+        .filter_map(|entry| match &entry.event {
+            CombatEvent::CellDamaged(damaged) => Some(damaged.amount),
+            _ => None,
+        })
+        .fold((0, 0.0), |(hits, total), amount| (hits + 1, total + amount))
+}
+
+#[test]
+fn a_seeded_wave_is_deterministic() {
+    let first = simulate_wave(base_config(42, 2));
+    let second = simulate_wave(base_config(42, 2));
+
+    assert!(!first.combat_log.entries.is_empty());
+    assert_eq!(summary(&first), summary(&second));
+}
+
+#[test]
+fn a_different_seed_plays_out_differently() {
+    let first = simulate_wave(base_config(42, 2));
+    let second = simulate_wave(base_config(7, 2));
+
+    assert_ne!(summary(&first), summary(&second));
+}
+
+#[test]
+fn damage_does_not_depend_on_the_frame_rate() {
+    let reports: Vec<WaveReport> = [5, 17, 50]
+        .into_iter()
+        .map(|frame_millis| {
+            simulate_wave(SimulationConfig {
+                frame_time: Duration::from_millis(frame_millis),
+                ..base_config(42, 2)
+            })
+        })
+        .collect();
+
+    let (hits, damage) = damage_dealt(&reports[0].combat_log);
+    assert!(hits > 0);
+    for report in reports.iter() {
+        assert_eq!(report.result, reports[0].result);
+        assert_eq!(damage_dealt(&report.combat_log), (hits, damage));
+    }
+}